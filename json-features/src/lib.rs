@@ -0,0 +1 @@
+//! Only holds tests, see the manifest.
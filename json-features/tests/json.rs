@@ -0,0 +1,114 @@
+//! `serde_json` is built with `arbitrary_precision` here, so it buffers
+//! numbers it cannot hold in 64 bits or an exact `f64` as maps under a
+//! private key, and with `raw_value`.
+
+#[test]
+fn json_arbitrary_precision() {
+    use serde_implicit::content::Content;
+
+    #[derive(serde_implicit_proc::Deserialize, Debug, PartialEq)]
+    enum Reading<'a> {
+        Sample {
+            #[serde_implicit(tag)]
+            sensor: u8,
+            value: f64,
+            total: u128,
+            exact: serde_json::Number,
+        },
+        #[serde_implicit(fallthrough)]
+        Other(#[serde(borrow)] Content<'a>),
+    }
+
+    let input = r#"{"sensor":3,"value":-1.5,"total":340282366920938463463374607431768211455,"exact":0.10000000000000000000001}"#;
+    assert_eq!(
+        serde_json::from_str::<Reading>(input).unwrap(),
+        Reading::Sample {
+            sensor: 3,
+            value: -1.5,
+            total: u128::MAX,
+            exact: "0.10000000000000000000001".parse().unwrap(),
+        }
+    );
+
+    let input = r#"{"sensor":"x","value":2.50}"#;
+    let err = serde_json::from_str::<Reading>(input).unwrap_err();
+    assert!(err.to_string().contains("string \"x\""), "{err}");
+
+    // A fallthrough re-emits numbers with the digits they were read with
+    let input = r#"{"price":0.10000000000000000000001,"count":123456789012345678901234567890,"ratio":2.50}"#;
+    let Reading::Other(content) = serde_json::from_str(input).unwrap() else {
+        panic!("expected the fallthrough");
+    };
+    assert_eq!(serde_json::to_string(&content).unwrap(), input);
+    assert_eq!(
+        serde_json::Value::from(content.clone()),
+        serde_json::from_str::<serde_json::Value>(input).unwrap()
+    );
+    assert_eq!(content.get("ratio").and_then(Content::as_f64), Some(2.5));
+    assert_eq!(
+        content.get("count").and_then(Content::as_u128),
+        Some(123456789012345678901234567890)
+    );
+    assert_eq!(content.get("count").and_then(Content::as_u64), None);
+}
+
+#[test]
+fn json_raw_value() {
+    use serde_json::value::RawValue;
+
+    #[derive(serde_implicit_proc::Deserialize, Debug)]
+    enum Event {
+        Click {
+            #[serde_implicit(tag)]
+            button: u8,
+            payload: Box<RawValue>,
+        },
+    }
+
+    let input = r#"{"button": 1, "payload": {"x": 1, "y": [2.50, null], "z": "a\tb"}}"#;
+    let Event::Click { button, payload } = serde_json::from_str(input).unwrap();
+    assert_eq!(button, 1);
+    // The raw text is serialized again from the buffered value, so it is
+    // compacted but keeps the digits of the numbers
+    assert_eq!(payload.get(), r#"{"x":1,"y":[2.50,null],"z":"a\tb"}"#);
+}
+
+#[test]
+fn json_value_arbitrary_precision() {
+    use serde_implicit::content::Content;
+
+    #[derive(serde_implicit_proc::Deserialize, Debug, PartialEq)]
+    enum Reading {
+        Sample {
+            #[serde_implicit(tag)]
+            sensor: u8,
+            total: u128,
+            delta: i128,
+            exact: serde_json::Number,
+        },
+    }
+
+    let input = r#"{"sensor":3,"total":340282366920938463463374607431768211455,"delta":-170141183460469231731687303715884105728,"exact":2.50}"#;
+    let value: serde_json::Value = serde_json::from_str(input).unwrap();
+    let expected = Reading::Sample {
+        sensor: 3,
+        total: u128::MAX,
+        delta: i128::MIN,
+        exact: "2.50".parse().unwrap(),
+    };
+    assert_eq!(
+        serde_json::from_value::<Reading>(value.clone()).unwrap(),
+        expected
+    );
+    assert_eq!(
+        serde_implicit::from_value_ref::<Reading>(&value).unwrap(),
+        expected
+    );
+
+    let content = Content::from(&value);
+    assert_eq!(content.get("total"), Some(&Content::U128(u128::MAX)));
+    assert_eq!(content.get("delta"), Some(&Content::I128(i128::MIN)));
+    assert_eq!(content.get("exact").and_then(Content::as_f64), Some(2.5));
+    assert_eq!(serde_json::Value::from(content), value);
+    assert_eq!(Content::from(value.clone()), Content::from(&value));
+}
@@ -1,48 +1,154 @@
 use std::collections::HashSet;
 
 use syn::{
-    DeriveInput, Error, Field, FieldsNamed, FieldsUnnamed, Generics, Ident, punctuated::Punctuated,
-    token::Comma,
+    DeriveInput, Error, Field, FieldsNamed, FieldsUnnamed, Generics, Ident, LitStr, Type,
+    punctuated::Punctuated, token::Comma,
 };
 
 pub struct Variant {
     pub ident: Ident,
     pub tag: Ident,
+    /// An explicit `null` under the tag key selects this variant.
+    pub allow_null: bool,
+    /// Resolves inputs carrying several tags, the highest priority wins.
+    pub priority: i32,
     pub fields: FieldsNamed,
+    /// Options of each field, in field order.
+    pub field_opts: Vec<FieldOpts>,
+}
+
+/// Options of a field of a struct variant.
+pub struct FieldOpts {
+    /// Integer map key given with `key = N`.
+    pub key: Option<u64>,
+    /// The field is deserialized from the entries no other field claimed.
+    pub flatten: bool,
+    /// Fields that must be present whenever this one is.
+    pub requires: Vec<Ident>,
+}
+
+impl Variant {
+    /// Integer map key of the tag field, if it has one.
+    pub fn tag_key(&self) -> Option<u64> {
+        self.fields
+            .named
+            .iter()
+            .zip(&self.field_opts)
+            .find(|(f, _)| f.ident.as_ref() == Some(&self.tag))
+            .and_then(|(_, opts)| opts.key)
+    }
 }
 
 pub struct TupleVariant {
     pub ident: Ident,
     pub fields: FieldsUnnamed,
     pub tag_index: usize,
+    /// Indices into nested sequences of the field at `tag_index` leading to
+    /// the tag, empty when the field itself is the tag.
+    pub tag_path: Vec<usize>,
+    /// Type the tag is checked against, when it is not the tagged field's type.
+    pub tag_type: Option<Type>,
+    /// Predicate deciding whether the tag matches, instead of its type.
+    pub tag_with: Option<syn::Path>,
     pub has_flatten: bool,
 }
 
 pub type Fields = FieldsNamed;
 
+/// A plain struct, deserialized from a map like a struct variant without a tag.
+pub struct Struct {
+    pub ident: Ident,
+    pub generics: Generics,
+    pub fields: FieldsNamed,
+    pub field_opts: Vec<FieldOpts>,
+    pub match_keys: MatchKeys,
+}
+
 pub struct Enum {
     pub ident: Ident,
     pub generics: Generics,
 
     pub vars: Style,
+    pub match_keys: MatchKeys,
+    /// Struct variants can also be given as a sequence of key-value pairs.
+    pub accept_seq: bool,
+    /// Bounds on the buffered input, overriding the thread's default.
+    pub limits: Limits,
+}
+
+/// `max_depth`, `max_len` and `max_bytes` given on the enum.
+#[derive(Clone, Copy, Default)]
+pub struct Limits {
+    pub max_depth: Option<usize>,
+    pub max_len: Option<usize>,
+    pub max_bytes: Option<usize>,
+}
+
+/// How the keys of the input are compared to tag and field names.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum MatchKeys {
+    #[default]
+    Exact,
+    /// Ignore case, `ImageUrl` matches `imageurl`.
+    CaseInsensitive,
+    /// Ignore case, `_` and `-`, so `ImageURL`, `image_url` and `imageUrl` all
+    /// match.
+    Normalized,
+}
+
+impl MatchKeys {
+    /// Fold a tag or field name the way input keys are folded before being
+    /// compared to it.
+    pub fn fold(self, name: &str) -> String {
+        match self {
+            MatchKeys::Exact => name.to_owned(),
+            MatchKeys::CaseInsensitive => name.chars().flat_map(char::to_lowercase).collect(),
+            MatchKeys::Normalized => name
+                .chars()
+                .filter(|c| !matches!(c, '_' | '-'))
+                .flat_map(char::to_lowercase)
+                .collect(),
+        }
+    }
 }
 
+#[allow(clippy::large_enum_variant)]
 pub enum Style {
     Tuple(Vec<TupleVariant>),
     Struct {
         variants: Vec<Variant>,
-        fallthrough: Option<Fallthrough>,
+        /// Trailing untagged variants, tried in order when no tag is found.
+        fallthrough: Vec<Fallthrough>,
     },
 }
 
-/// A fallthrough variant for `serde-implicit`
+/// A fallthrough variant for `serde-implicit`, either `Var { field: T }` or
+/// the newtype `Var(T)`.
 pub struct Fallthrough {
     pub ident: Ident,
     pub field: Field,
+    /// Field marked `#[serde_implicit(keys)]`, receiving the keys of the
+    /// unmatched input.
+    pub keys: Option<Ident>,
 }
 
-pub const TAG: &'static str = "tag";
-pub const FLATTEN: &'static str = "flatten";
+pub const TAG: &str = "tag";
+pub const FLATTEN: &str = "flatten";
+pub const PATH: &str = "path";
+pub const TAG_TYPE: &str = "tag_type";
+pub const TAG_WITH: &str = "tag_with";
+pub const FALLTHROUGH: &str = "fallthrough";
+pub const KEYS: &str = "keys";
+pub const ALLOW_NULL: &str = "allow_null";
+pub const PRIORITY: &str = "priority";
+pub const MATCH_KEYS: &str = "match_keys";
+pub const KEY: &str = "key";
+pub const ACCEPT_SEQ: &str = "accept_seq";
+pub const REQUIRES: &str = "requires";
+pub const BORROW: &str = "borrow";
+pub const MAX_DEPTH: &str = "max_depth";
+pub const MAX_LEN: &str = "max_len";
+pub const MAX_BYTES: &str = "max_bytes";
 
 pub fn parse_data(input: DeriveInput) -> syn::Result<Enum> {
     let enum_ = match input.data {
@@ -55,51 +161,231 @@ pub fn parse_data(input: DeriveInput) -> syn::Result<Enum> {
         }
     };
 
+    let EnumAttrs {
+        match_keys,
+        accept_seq,
+        limits,
+    } = parse_enum_attrs(&input.attrs)?;
+
+    let mut generics = input.generics;
+    add_borrow_bounds(&mut generics, enum_.variants.iter().flat_map(|v| &v.fields))?;
+
     let variants = match enum_.variants.first().map(|v| &v.fields) {
-        Some(syn::Fields::Named(_)) => parse_struct_variants(enum_.variants)?,
-        Some(syn::Fields::Unnamed(_)) => parse_enum_variants(enum_.variants)?,
+        Some(syn::Fields::Named(_)) => parse_struct_variants(enum_.variants, match_keys)?,
+        Some(syn::Fields::Unnamed(_)) => {
+            if match_keys != MatchKeys::Exact || accept_seq {
+                return Err(Error::new_spanned(
+                    &input.ident,
+                    "`match_keys` and `accept_seq` only apply to enums of struct variants",
+                ));
+            }
+            parse_enum_variants(enum_.variants)?
+        }
         Some(syn::Fields::Unit) => todo!(),
         None => Style::Tuple(vec![]),
     };
 
     Ok(Enum {
         ident: input.ident,
-        generics: input.generics,
+        generics,
         vars: variants,
+        match_keys,
+        accept_seq,
+        limits,
     })
 }
 
-enum VarOrFall {
-    Var(Variant),
-    Fall(Fallthrough),
+pub fn parse_struct(input: DeriveInput) -> syn::Result<Struct> {
+    let named = match input.data {
+        syn::Data::Struct(syn::DataStruct {
+            fields: syn::Fields::Named(named),
+            ..
+        }) => named,
+        _ => {
+            return Err(Error::new_spanned(
+                input,
+                "`serde_implicit` can only `Deserialize` structs with named fields",
+            ));
+        }
+    };
+
+    let EnumAttrs {
+        match_keys,
+        accept_seq,
+        limits,
+    } = parse_enum_attrs(&input.attrs)?;
+    if accept_seq {
+        return Err(Error::new_spanned(
+            &input.ident,
+            "`accept_seq` only applies to enums of struct variants",
+        ));
+    }
+    if limits.max_depth.is_some() || limits.max_len.is_some() || limits.max_bytes.is_some() {
+        return Err(Error::new_spanned(
+            &input.ident,
+            "a struct does not buffer its input, `max_depth`, `max_len` and `max_bytes` only apply to enums",
+        ));
+    }
+
+    let mut field_opts = vec![];
+    for field in &named.named {
+        let attrs = parse_struct_field_attrs(field)?;
+        if attrs.tag || attrs.allow_null || attrs.priority.is_some() || attrs.keys {
+            return Err(Error::new_spanned(
+                field,
+                "a struct has no implicit tag, `tag` and its options only apply to enum variants",
+            ));
+        }
+        field_opts.push(parse_field_opts(field, attrs, &field_opts)?);
+    }
+    check_requires(&named, &field_opts)?;
+
+    let mut generics = input.generics;
+    add_borrow_bounds(&mut generics, &named.named)?;
+
+    if match_keys != MatchKeys::Exact {
+        check_folded_names(
+            named.named.iter().filter_map(|f| f.ident.as_ref()),
+            match_keys,
+            "fields",
+        )?;
+    }
+
+    Ok(Struct {
+        ident: input.ident,
+        generics,
+        fields: named,
+        field_opts,
+        match_keys,
+    })
 }
 
-fn parse_struct_variants(mut enum_variants: Punctuated<syn::Variant, Comma>) -> syn::Result<Style> {
-    let mut variants = vec![];
+/// `serde_implicit` attributes found on the enum itself.
+#[derive(Default)]
+struct EnumAttrs {
+    match_keys: MatchKeys,
+    accept_seq: bool,
+    limits: Limits,
+}
 
-    let last_var = enum_variants.pop();
+fn parse_enum_attrs(attrs: &[syn::Attribute]) -> syn::Result<EnumAttrs> {
+    let mut enum_attrs = EnumAttrs::default();
+    for attr in attrs.iter().filter(|a| a.path().is_ident("serde_implicit")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident(ACCEPT_SEQ) {
+                enum_attrs.accept_seq = true;
+                Ok(())
+            } else if let Some(limit) = [
+                (MAX_DEPTH, &mut enum_attrs.limits.max_depth),
+                (MAX_LEN, &mut enum_attrs.limits.max_len),
+                (MAX_BYTES, &mut enum_attrs.limits.max_bytes),
+            ]
+            .into_iter()
+            .find_map(|(name, limit)| meta.path.is_ident(name).then_some(limit))
+            {
+                let lit: syn::LitInt = meta.value()?.parse()?;
+                *limit = Some(lit.base10_parse()?);
+                Ok(())
+            } else if meta.path.is_ident(MATCH_KEYS) {
+                let lit: LitStr = meta.value()?.parse()?;
+                enum_attrs.match_keys = match &*lit.value() {
+                    "exact" => MatchKeys::Exact,
+                    "case_insensitive" => MatchKeys::CaseInsensitive,
+                    "normalized" => MatchKeys::Normalized,
+                    _ => {
+                        return Err(Error::new_spanned(
+                            lit,
+                            "expected `exact`, `case_insensitive` or `normalized`",
+                        ));
+                    }
+                };
+                Ok(())
+            } else {
+                Err(Error::new_spanned(
+                    attr,
+                    "unknown attribute, expected `match_keys`, `accept_seq`, `max_depth`, `max_len` or `max_bytes`",
+                ))
+            }
+        })?;
+    }
+    Ok(enum_attrs)
+}
 
-    for v in enum_variants {
-        let variant = parse_variant(v)?;
-        variants.push(variant);
+/// Reject names that can no longer be told apart once folded by `match_keys`.
+fn check_folded_names<'a>(
+    names: impl IntoIterator<Item = &'a Ident>,
+    match_keys: MatchKeys,
+    what: &str,
+) -> syn::Result<()> {
+    let mut seen = std::collections::HashMap::new();
+    for name in names {
+        let folded = match_keys.fold(&name.to_string());
+        if let Some(prev) = seen.insert(folded, name) {
+            return Err(Error::new_spanned(
+                name,
+                format!("{what} `{prev}` and `{name}` cannot be told apart with this `match_keys`"),
+            ));
+        }
     }
+    Ok(())
+}
 
-    let mut fallthrough = None;
+#[allow(clippy::large_enum_variant)]
+enum VarOrFall {
+    Var(Variant),
+    Fall(Fallthrough),
+}
 
-    if let Some(var) = last_var {
-        let var_or_fall = parse_variant_or_fallthrough(&var.into_value(), true)?;
+fn parse_struct_variants(
+    enum_variants: Punctuated<syn::Variant, Comma>,
+    match_keys: MatchKeys,
+) -> syn::Result<Style> {
+    let mut variants = vec![];
+    let mut fallthrough = vec![];
 
-        match var_or_fall {
-            VarOrFall::Var(var) => variants.push(var),
-            VarOrFall::Fall(fall) => fallthrough = Some(fall),
+    let last_ix = enum_variants.len().saturating_sub(1);
+
+    for (ix, v) in enum_variants.into_iter().enumerate() {
+        match parse_variant_or_fallthrough(&v, ix == last_ix)? {
+            VarOrFall::Var(var) => {
+                if !fallthrough.is_empty() {
+                    return Err(Error::new_spanned(
+                        &var.ident,
+                        "fallthrough variants must appear after all tagged variants in the enum definition",
+                    ));
+                }
+                variants.push(var)
+            }
+            VarOrFall::Fall(fall) => fallthrough.push(fall),
         }
     }
     let mut unique_tags = HashSet::new();
 
+    let mut unique_tag_keys = HashSet::new();
+
     for v in &variants {
         if !unique_tags.insert(v.tag.clone()) {
             return Err(Error::new_spanned(v.tag.clone(), "duplicate tags found"));
         }
+        if let Some(key) = v.tag_key()
+            && !unique_tag_keys.insert(key)
+        {
+            return Err(Error::new_spanned(
+                v.tag.clone(),
+                format!("duplicate tag `key = {key}` found"),
+            ));
+        }
+    }
+
+    if match_keys != MatchKeys::Exact {
+        check_folded_names(variants.iter().map(|v| &v.tag), match_keys, "tags")?;
+        for v in &variants {
+            check_folded_names(
+                v.fields.named.iter().filter_map(|f| f.ident.as_ref()),
+                match_keys,
+                "fields",
+            )?;
+        }
     }
 
     Ok(Style::Struct {
@@ -108,50 +394,70 @@ fn parse_struct_variants(mut enum_variants: Punctuated<syn::Variant, Comma>) ->
     })
 }
 
-fn has_tag_attribute(field: &Field) -> syn::Result<bool> {
-    let mut has_tag = false;
+/// `serde_implicit` attributes found on a field of a tuple variant.
+#[derive(Default)]
+struct TupleFieldAttrs {
+    tag: bool,
+    flatten: bool,
+    path: Option<Vec<usize>>,
+    tag_type: Option<Type>,
+    tag_with: Option<syn::Path>,
+}
+
+fn parse_tuple_field_attrs(field: &Field) -> syn::Result<TupleFieldAttrs> {
+    if has_serde_attr(field, FLATTEN)? {
+        return Err(Error::new_spanned(
+            field,
+            "`serde(flatten)` is only supported on fields of struct variants",
+        ));
+    }
+    let mut attrs = TupleFieldAttrs::default();
     for attr in &field.attrs {
         if attr.path().is_ident("serde_implicit") {
             attr.parse_nested_meta(|meta| {
                 if meta.path.is_ident(TAG) {
-                    has_tag = true;
+                    attrs.tag = true;
                     Ok(())
                 } else if meta.path.is_ident(FLATTEN) {
-                    // Allow flatten in the same pass, will be validated later
+                    attrs.flatten = true;
                     Ok(())
-                } else {
-                    Err(Error::new_spanned(
-                        attr,
-                        "unknown attribute, expected `tag` or `flatten`",
-                    ))
-                }
-            })?;
-        }
-    }
-    Ok(has_tag)
-}
-
-fn has_flatten_attribute(field: &Field) -> syn::Result<bool> {
-    let mut has_flatten = false;
-    for attr in &field.attrs {
-        if attr.path().is_ident("serde_implicit") {
-            attr.parse_nested_meta(|meta| {
-                if meta.path.is_ident(FLATTEN) {
-                    has_flatten = true;
+                } else if meta.path.is_ident(PATH) {
+                    let lit: LitStr = meta.value()?.parse()?;
+                    attrs.path = Some(parse_tag_path(&lit)?);
+                    Ok(())
+                } else if meta.path.is_ident(TAG_TYPE) {
+                    let lit: LitStr = meta.value()?.parse()?;
+                    attrs.tag_type = Some(lit.parse()?);
                     Ok(())
-                } else if meta.path.is_ident(TAG) {
-                    // Allow tag in the same pass, will be validated later
+                } else if meta.path.is_ident(TAG_WITH) {
+                    let lit: LitStr = meta.value()?.parse()?;
+                    attrs.tag_with = Some(lit.parse()?);
                     Ok(())
                 } else {
                     Err(Error::new_spanned(
                         attr,
-                        "unknown attribute, expected `tag` or `flatten`",
+                        "unknown attribute, expected `tag`, `flatten`, `path`, `tag_type` or `tag_with`",
                     ))
                 }
             })?;
         }
     }
-    Ok(has_flatten)
+    Ok(attrs)
+}
+
+/// Parse a dotted path of sequence indices, like `"0.1"`.
+fn parse_tag_path(lit: &LitStr) -> syn::Result<Vec<usize>> {
+    lit.value()
+        .split('.')
+        .map(|ix| {
+            ix.trim().parse::<usize>().map_err(|_| {
+                Error::new_spanned(
+                    lit,
+                    "expected a path of sequence indices separated by `.`, like \"0.1\"",
+                )
+            })
+        })
+        .collect()
 }
 
 fn parse_enum_variants(enum_variants: Punctuated<syn::Variant, Comma>) -> syn::Result<Style> {
@@ -159,6 +465,12 @@ fn parse_enum_variants(enum_variants: Punctuated<syn::Variant, Comma>) -> syn::R
     let mut seen_flatten = false;
 
     for v in enum_variants {
+        if has_fallthrough_attribute(&v)? {
+            return Err(Error::new_spanned(
+                v,
+                "`#[serde_implicit(fallthrough)]` is only supported in enums of struct variants",
+            ));
+        }
         let variant_ident = v.ident.clone();
         let variant = match v.fields {
             syn::Fields::Named(_) => {
@@ -191,10 +503,43 @@ fn parse_enum_variant(
     // Find which field has the tag or flatten attribute
     let mut tag_index = None;
     let mut flatten_index = None;
+    let mut tag_path = vec![];
+    let mut tag_type = None;
+    let mut tag_with = None;
 
     for (i, field) in fields_unnamed.unnamed.iter().enumerate() {
-        let has_tag = has_tag_attribute(field)?;
-        let has_flatten = has_flatten_attribute(field)?;
+        let attrs = parse_tuple_field_attrs(field)?;
+        // A predicate marks the field as the tag on its own
+        let has_tag = attrs.tag || attrs.tag_with.is_some();
+        let has_flatten = attrs.flatten;
+
+        if !has_tag && (attrs.path.is_some() || attrs.tag_type.is_some()) {
+            return Err(Error::new_spanned(
+                field,
+                "`path` and `tag_type` can only be used on the `#[serde_implicit(tag)]` field",
+            ));
+        }
+
+        if attrs.tag_type.is_some() && attrs.tag_with.is_some() {
+            return Err(Error::new_spanned(
+                field,
+                "`tag_type` and `tag_with` are mutually exclusive",
+            ));
+        }
+
+        if attrs.path.is_some() && attrs.tag_type.is_none() && attrs.tag_with.is_none() {
+            return Err(Error::new_spanned(
+                field,
+                "a nested tag needs a `tag_type` or a `tag_with` predicate to check the value at `path`",
+            ));
+        }
+
+        if attrs.tag_type.is_some() && attrs.path.is_none() {
+            return Err(Error::new_spanned(
+                field,
+                "`tag_type` can only be used together with `path`",
+            ));
+        }
 
         // Validate tag and flatten are mutually exclusive
         if has_tag && has_flatten {
@@ -212,6 +557,9 @@ fn parse_enum_variant(
                 ));
             }
             tag_index = Some(i);
+            tag_path = attrs.path.unwrap_or_default();
+            tag_type = attrs.tag_type;
+            tag_with = attrs.tag_with;
         }
 
         if has_flatten {
@@ -251,11 +599,43 @@ fn parse_enum_variant(
         ident: variant_ident,
         fields: fields_unnamed,
         tag_index: tag_index.unwrap_or(0), // Default to position 0
+        tag_path,
+        tag_type,
+        tag_with,
         has_flatten,
     })
 }
 
-fn parse_variant_or_fallthrough(v: &syn::Variant, can_fallthrough: bool) -> syn::Result<VarOrFall> {
+/// Whether the variant is marked `#[serde_implicit(fallthrough)]`.
+fn has_fallthrough_attribute(v: &syn::Variant) -> syn::Result<bool> {
+    let mut fallthrough = false;
+    for attr in v
+        .attrs
+        .iter()
+        .filter(|a| a.path().is_ident("serde_implicit"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident(FALLTHROUGH) {
+                fallthrough = true;
+                Ok(())
+            } else {
+                Err(Error::new_spanned(
+                    attr,
+                    "unknown attribute, expected `fallthrough`",
+                ))
+            }
+        })?;
+    }
+    Ok(fallthrough)
+}
+
+/// Parse a variant of a struct enum, `is_last` only decides whether a missing
+/// tag suggests the fallthrough marker.
+fn parse_variant_or_fallthrough(v: &syn::Variant, is_last: bool) -> syn::Result<VarOrFall> {
+    if has_fallthrough_attribute(v)? {
+        return parse_fallthrough(v).map(VarOrFall::Fall);
+    }
+
     let named = match &v.fields {
         syn::Fields::Named(named) => named,
         syn::Fields::Unit | syn::Fields::Unnamed(_) => {
@@ -268,55 +648,59 @@ fn parse_variant_or_fallthrough(v: &syn::Variant, can_fallthrough: bool) -> syn:
 
     // Find all fields with #[serde_implicit(tag)] attribute
     let mut tagged_fields = vec![];
+    let mut allow_null = false;
+    let mut priority = 0;
+    let mut field_opts: Vec<FieldOpts> = vec![];
 
     for field in &named.named {
-        let mut has_tag = false;
-        field
-            .attrs
-            .iter()
-            .filter(|a| a.path().is_ident("serde_implicit"))
-            .try_for_each(|attr| {
-                attr.parse_nested_meta(|meta| {
-                    if meta.path.is_ident(TAG) {
-                        has_tag = true;
-                        Ok(())
-                    } else {
-                        Err(Error::new_spanned(attr, "omg"))
-                    }
-                })
-            })?;
-
-        if has_tag {
+        let attrs = parse_struct_field_attrs(field)?;
+        if attrs.flatten && attrs.tag {
+            return Err(Error::new_spanned(
+                field,
+                "a flattened field has no key of its own, it cannot be the tag or have a `key`",
+            ));
+        }
+        if attrs.keys {
+            return Err(Error::new_spanned(
+                field,
+                "`#[serde_implicit(keys)]` can only be used in the fallthrough variant",
+            ));
+        }
+        if (attrs.allow_null || attrs.priority.is_some()) && !attrs.tag {
+            return Err(Error::new_spanned(
+                field,
+                "`allow_null` and `priority` can only be used on the `#[serde_implicit(tag)]` field",
+            ));
+        }
+        if attrs.tag && !attrs.allow_null && is_option(&field.ty) {
+            return Err(Error::new_spanned(
+                &field.ty,
+                "an `Option` tag is never selected by a `null` value, use `#[serde_implicit(tag, allow_null)]` to select the variant whenever the key is present",
+            ));
+        }
+        if attrs.tag {
             tagged_fields.push(field);
+            allow_null = attrs.allow_null;
+            priority = attrs.priority.unwrap_or(0);
         }
+        field_opts.push(parse_field_opts(field, attrs, &field_opts)?);
     }
+    check_requires(named, &field_opts)?;
 
-    let tag;
-    match tagged_fields.len() {
-        0 => {
-            if !can_fallthrough {
-                return Err(Error::new_spanned(
-                    named,
-                    "missing `#[serde_implicit(tag)]`",
-                ));
-            };
-
-            if named.named.len() != 1 {
-                return Err(Error::new_spanned(
-                    v,
-                    "fallthrough must have exactly one field",
-                ));
-            }
-
-            return Ok(VarOrFall::Fall(Fallthrough {
-                ident: v.ident.clone(),
-                field: named.named.last().cloned().unwrap(),
-            }));
+    let tag = match tagged_fields.len() {
+        0 if is_last => {
+            return Err(Error::new_spanned(
+                named,
+                "missing `#[serde_implicit(tag)]`, or mark the variant `#[serde_implicit(fallthrough)]` to catch untagged input",
+            ));
         }
-
-        1 => {
-            tag = tagged_fields[0].ident.clone().unwrap();
+        0 => {
+            return Err(Error::new_spanned(
+                named,
+                "missing `#[serde_implicit(tag)]`",
+            ));
         }
+        1 => tagged_fields[0].ident.clone().unwrap(),
         _ => {
             return Err(Error::new_spanned(
                 named,
@@ -328,13 +712,327 @@ fn parse_variant_or_fallthrough(v: &syn::Variant, can_fallthrough: bool) -> syn:
     Ok(VarOrFall::Var(Variant {
         ident: v.ident.clone(),
         tag,
+        allow_null,
+        priority,
         fields: named.clone(),
+        field_opts,
     }))
 }
 
-fn parse_variant(v: syn::Variant) -> syn::Result<Variant> {
-    match parse_variant_or_fallthrough(&v, false)? {
-        VarOrFall::Var(v) => Ok(v),
-        _ => unreachable!(),
+fn parse_fallthrough(v: &syn::Variant) -> syn::Result<Fallthrough> {
+    let fields = match &v.fields {
+        syn::Fields::Named(named) => &named.named,
+        syn::Fields::Unnamed(unnamed) => &unnamed.unnamed,
+        syn::Fields::Unit => {
+            return Err(Error::new_spanned(
+                v,
+                "fallthrough must have exactly one field",
+            ));
+        }
+    };
+
+    let mut value = None;
+    let mut keys = None;
+    for field in fields {
+        let attrs = parse_struct_field_attrs(field)?;
+        if attrs.tag
+            || attrs.allow_null
+            || attrs.priority.is_some()
+            || attrs.key.is_some()
+            || !attrs.requires.is_empty()
+        {
+            return Err(Error::new_spanned(
+                field,
+                "the fallthrough variant cannot have a `#[serde_implicit(tag)]` or its options",
+            ));
+        }
+        if attrs.flatten {
+            return Err(Error::new_spanned(
+                field,
+                "the fallthrough variant already receives the whole input, it cannot be flattened",
+            ));
+        }
+        if attrs.keys && field.ident.is_some() && keys.is_none() {
+            keys = field.ident.clone();
+        } else if !attrs.keys && value.is_none() {
+            value = Some(field.clone());
+        } else {
+            return Err(Error::new_spanned(
+                v,
+                "fallthrough must have exactly one field, and optionally a named `#[serde_implicit(keys)]` field",
+            ));
+        }
+    }
+
+    let Some(field) = value else {
+        return Err(Error::new_spanned(
+            v,
+            "fallthrough must have exactly one field",
+        ));
+    };
+
+    Ok(Fallthrough {
+        ident: v.ident.clone(),
+        field,
+        keys,
+    })
+}
+
+/// Options of a field in a map-shaped struct or variant, checked against the
+/// fields before it.
+fn parse_field_opts(
+    field: &Field,
+    attrs: StructFieldAttrs,
+    before: &[FieldOpts],
+) -> syn::Result<FieldOpts> {
+    if let Some(key) = attrs.key
+        && before.iter().any(|opts| opts.key == Some(key))
+    {
+        return Err(Error::new_spanned(
+            field,
+            format!("duplicate `key = {key}` in this variant"),
+        ));
+    }
+    if attrs.flatten && attrs.key.is_some() {
+        return Err(Error::new_spanned(
+            field,
+            "a flattened field has no key of its own, it cannot be the tag or have a `key`",
+        ));
+    }
+    Ok(FieldOpts {
+        key: attrs.key,
+        flatten: attrs.flatten,
+        requires: attrs.requires,
+    })
+}
+
+/// Check that `requires` names other fields that are looked up by key.
+fn check_requires(fields: &FieldsNamed, field_opts: &[FieldOpts]) -> syn::Result<()> {
+    let find = |name: &Ident| {
+        fields
+            .named
+            .iter()
+            .zip(field_opts)
+            .find(|(f, _)| f.ident.as_ref() == Some(name))
+    };
+    for (field, opts) in fields.named.iter().zip(field_opts) {
+        if opts.flatten && !opts.requires.is_empty() {
+            return Err(Error::new_spanned(
+                field,
+                "a flattened field is never present on its own, it cannot use `requires`",
+            ));
+        }
+        for name in &opts.requires {
+            match find(name) {
+                None => {
+                    return Err(Error::new_spanned(name, format!("no field named `{name}`")));
+                }
+                Some((other, _)) if other.ident == field.ident => {
+                    return Err(Error::new_spanned(name, "a field cannot require itself"));
+                }
+                Some((_, other)) if other.flatten => {
+                    return Err(Error::new_spanned(
+                        name,
+                        "a flattened field is never present on its own, it cannot be required",
+                    ));
+                }
+                Some(_) => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whether `ty` is spelled as `Option<_>`, possibly through a path like
+/// `std::option::Option<_>`.
+pub fn is_option(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) if path.qself.is_none() => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|seg| seg.ident == "Option"),
+        Type::Group(group) => is_option(&group.elem),
+        Type::Paren(paren) => is_option(&paren.elem),
+        _ => false,
+    }
+}
+
+/// `serde_implicit` attributes found on a field of a struct variant.
+#[derive(Default)]
+struct StructFieldAttrs {
+    tag: bool,
+    keys: bool,
+    allow_null: bool,
+    priority: Option<i32>,
+    key: Option<u64>,
+    flatten: bool,
+    requires: Vec<Ident>,
+}
+
+fn parse_struct_field_attrs(field: &Field) -> syn::Result<StructFieldAttrs> {
+    let mut attrs = StructFieldAttrs {
+        flatten: has_serde_attr(field, FLATTEN)?,
+        ..Default::default()
+    };
+    field
+        .attrs
+        .iter()
+        .filter(|a| a.path().is_ident("serde_implicit"))
+        .try_for_each(|attr| {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident(TAG) {
+                    attrs.tag = true;
+                    Ok(())
+                } else if meta.path.is_ident(KEYS) {
+                    attrs.keys = true;
+                    Ok(())
+                } else if meta.path.is_ident(ALLOW_NULL) {
+                    attrs.allow_null = true;
+                    Ok(())
+                } else if meta.path.is_ident(PRIORITY) {
+                    let lit: syn::LitInt = meta.value()?.parse()?;
+                    attrs.priority = Some(lit.base10_parse()?);
+                    Ok(())
+                } else if meta.path.is_ident(KEY) {
+                    let lit: syn::LitInt = meta.value()?.parse()?;
+                    attrs.key = Some(lit.base10_parse()?);
+                    Ok(())
+                } else if meta.path.is_ident(FLATTEN) {
+                    attrs.flatten = true;
+                    Ok(())
+                } else if meta.path.is_ident(REQUIRES) {
+                    let lit: LitStr = meta.value()?.parse()?;
+                    for name in lit.value().split(',') {
+                        let mut ident: Ident = syn::parse_str(name.trim()).map_err(|_| {
+                            Error::new_spanned(&lit, "expected a comma-separated list of fields")
+                        })?;
+                        ident.set_span(lit.span());
+                        attrs.requires.push(ident);
+                    }
+                    Ok(())
+                } else {
+                    Err(Error::new_spanned(
+                        attr,
+                        "unknown attribute, expected `tag`, `keys`, `allow_null`, `priority`, `key`, `flatten` or `requires`",
+                    ))
+                }
+            })
+        })?;
+    Ok(attrs)
+}
+
+/// Require `'de` to outlive the lifetimes fields borrow from the input, like
+/// `serde_derive`: those of `&str` and `&[u8]` fields, and every lifetime of a
+/// field marked `#[serde(borrow)]`. Other lifetimes stay unbounded so that
+/// types like `Cow<'a, str>` can still be deserialized from owned input.
+fn add_borrow_bounds<'a>(
+    generics: &mut Generics,
+    fields: impl IntoIterator<Item = &'a Field>,
+) -> syn::Result<()> {
+    let mut borrowed = Vec::new();
+    for field in fields {
+        if has_serde_attr(field, BORROW)? {
+            collect_lifetimes(&field.ty, &mut borrowed);
+        } else if let Type::Reference(reference) = &field.ty
+            && let Some(lifetime) = &reference.lifetime
+            && is_borrowable(&reference.elem)
+            && !borrowed.contains(lifetime)
+        {
+            borrowed.push(lifetime.clone());
+        }
+    }
+
+    let where_clause = generics.make_where_clause();
+    for lifetime in borrowed {
+        where_clause
+            .predicates
+            .push(syn::parse_quote!('de: #lifetime));
+    }
+    Ok(())
+}
+
+/// Whether a reference to `ty` can borrow from the input, `str` or `[u8]`.
+fn is_borrowable(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => path.qself.is_none() && path.path.is_ident("str"),
+        Type::Slice(slice) => {
+            matches!(&*slice.elem, Type::Path(path) if path.qself.is_none() && path.path.is_ident("u8"))
+        }
+        Type::Group(group) => is_borrowable(&group.elem),
+        Type::Paren(paren) => is_borrowable(&paren.elem),
+        _ => false,
+    }
+}
+
+fn collect_lifetimes(ty: &Type, out: &mut Vec<syn::Lifetime>) {
+    fn push(lifetime: &syn::Lifetime, out: &mut Vec<syn::Lifetime>) {
+        if lifetime.ident != "static" && !out.contains(lifetime) {
+            out.push(lifetime.clone());
+        }
+    }
+    match ty {
+        Type::Reference(reference) => {
+            if let Some(lifetime) = &reference.lifetime {
+                push(lifetime, out);
+            }
+            collect_lifetimes(&reference.elem, out);
+        }
+        Type::Path(path) => {
+            for seg in &path.path.segments {
+                if let syn::PathArguments::AngleBracketed(args) = &seg.arguments {
+                    for arg in &args.args {
+                        match arg {
+                            syn::GenericArgument::Lifetime(lifetime) => push(lifetime, out),
+                            syn::GenericArgument::Type(ty) => collect_lifetimes(ty, out),
+                            _ => {}
+                        }
+                    }
+                }
+            }
+        }
+        Type::Slice(slice) => collect_lifetimes(&slice.elem, out),
+        Type::Array(array) => collect_lifetimes(&array.elem, out),
+        Type::Tuple(tuple) => tuple.elems.iter().for_each(|ty| collect_lifetimes(ty, out)),
+        Type::Group(group) => collect_lifetimes(&group.elem, out),
+        Type::Paren(paren) => collect_lifetimes(&paren.elem, out),
+        _ => {}
+    }
+}
+
+/// `serde` field attributes that change how a field is deserialized but
+/// that this derive does not implement. Attributes that only affect a derived
+/// `Serialize`, like `skip_serializing_if`, are left alone.
+const IGNORED_SERDE_ATTRS: &[&str] = &[
+    "rename",
+    "alias",
+    "default",
+    "deserialize_with",
+    "with",
+    "skip_deserializing",
+    "skip",
+    "bound",
+];
+
+/// Whether the field has serde's own `#[serde(name)]`. `flatten` and
+/// `borrow` are honored so that fields shared with a derived `Serialize`
+/// need not be repeated. Attributes that would change deserialization but
+/// would be silently ignored are rejected.
+fn has_serde_attr(field: &Field, name: &str) -> syn::Result<bool> {
+    let mut found = false;
+    for attr in field.attrs.iter().filter(|a| a.path().is_ident("serde")) {
+        let metas = attr.parse_args_with(Punctuated::<syn::Meta, Comma>::parse_terminated)?;
+        for meta in &metas {
+            if let Some(ignored) = IGNORED_SERDE_ATTRS.iter().find(|a| meta.path().is_ident(a)) {
+                return Err(Error::new_spanned(
+                    meta.path(),
+                    format!(
+                        "`serde({ignored})` is not supported, it would be ignored when deserializing"
+                    ),
+                ));
+            }
+            found |= meta.path().is_ident(name);
+        }
     }
+    Ok(found)
 }
@@ -1,14 +1,18 @@
-use annoying::{ImplGenerics, TypeGenerics};
+pub(crate) use annoying::{ImplGenerics, TypeGenerics};
 use proc_macro2::{Literal, TokenStream};
 use quote::{format_ident, quote};
 use syn::{Ident, WhereClause};
 
 use crate::{
-    ast::{self, Fallthrough, Style},
+    ast::{self, Fallthrough, FieldOpts, MatchKeys, Style},
     tuple_enum::expand_tuple_enum,
 };
 
 pub fn expand_derive_serialize(input: syn::DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    if let syn::Data::Struct(_) = input.data {
+        return expand_struct(ast::parse_struct(input)?);
+    }
+
     let data_enum = ast::parse_data(input)?;
 
     // if we need the 'de lifetime do same trick as serde
@@ -21,17 +25,59 @@ pub fn expand_derive_serialize(input: syn::DeriveInput) -> syn::Result<proc_macr
     let impl_generics = ImplGenerics(&data_enum.generics);
     let ty_generics = TypeGenerics(&data_enum.generics);
 
-    let body = match data_enum.vars {
+    let limits = buffer_limits(data_enum.limits);
+
+    let (body, ref_body) = match &data_enum.vars {
         Style::Struct {
             variants,
             fallthrough,
-        } => expand_struct_enum(
-            &data_enum.ident,
-            (impl_generics, ty_generics, where_clause),
-            &variants,
-            fallthrough.as_ref(),
-        )?,
-        Style::Tuple(variants) => expand_tuple_enum(&data_enum.ident, &variants)?,
+        } => {
+            let expand = |by_ref| {
+                expand_struct_enum(
+                    &data_enum.ident,
+                    (impl_generics, ty_generics, where_clause),
+                    variants,
+                    fallthrough,
+                    StructEnumOptions {
+                        match_keys: data_enum.match_keys,
+                        accept_seq: data_enum.accept_seq,
+                        by_ref,
+                        limits: &limits,
+                    },
+                )
+            };
+            let body = expand(false)?;
+            let ref_body = expand(true)?;
+            (
+                quote! {
+                    #enum_variant
+
+                    #body
+                },
+                quote! {
+                    #enum_variant
+
+                    #ref_body
+                },
+            )
+        }
+        // The trials already run against buffered content
+        Style::Tuple(variants) => (
+            quote! {
+                let __content = {
+                    let __guard = serde_implicit::__private::BufferGuard::enter(#limits);
+                    <serde_implicit::__private::Content as serde::Deserialize>::deserialize(
+                        __deserializer,
+                    )?
+                };
+                <Self as serde_implicit::FromContentRef<'de>>::from_content_ref(&__content)
+            },
+            expand_tuple_enum(
+                &data_enum.ident,
+                (impl_generics, ty_generics, where_clause),
+                variants,
+            )?,
+        ),
     };
 
     Ok(quote! {
@@ -40,15 +86,100 @@ pub fn expand_derive_serialize(input: syn::DeriveInput) -> syn::Result<proc_macr
             fn deserialize<__D>(__deserializer: __D) -> Result<Self, __D::Error>
             where __D: serde::Deserializer<'de>
             {
-               #enum_variant
+               #body
+            }
+
+        }
+
+        #[automatically_derived]
+        impl <'de, #impl_generics > serde_implicit::FromContentRef<'de> for #this_type < #ty_generics > #where_clause {
+            fn from_content_ref<__E>(
+                __content: &serde_implicit::__private::Content<'de>,
+            ) -> Result<Self, __E>
+            where __E: serde::de::Error
+            {
+               #ref_body
+            }
+        }
+    })
+}
+
+/// Plain structs have no tag, they reuse the struct variant deserializer for
+/// `requires`, integer keys and key matching.
+fn expand_struct(struct_: ast::Struct) -> syn::Result<proc_macro2::TokenStream> {
+    let this_type = &struct_.ident;
+    let (_, _, where_clause) = struct_.generics.split_for_impl();
+    let impl_generics = ImplGenerics(&struct_.generics);
+    let ty_generics = TypeGenerics(&struct_.generics);
+
+    let block = deserialize_fields(&struct_.fields, &struct_.field_opts, struct_.match_keys);
+    let body = implement_variant_deserializer(
+        None,
+        &struct_.fields,
+        &struct_.field_opts,
+        this_type,
+        &impl_generics,
+        &ty_generics,
+        &where_clause,
+    );
+
+    Ok(quote! {
+        #[automatically_derived]
+        impl <'de, #impl_generics > serde::Deserialize<'de> for #this_type < #ty_generics > #where_clause {
+            fn deserialize<__D>(__deserializer: __D) -> Result<Self, __D::Error>
+            where __D: serde::Deserializer<'de>
+            {
+               #block
 
                #body
             }
+        }
+
+        #[automatically_derived]
+        impl <'de, #impl_generics > serde_implicit::FromContentRef<'de> for #this_type < #ty_generics > #where_clause {
+            fn from_content_ref<__E>(
+                __content: &serde_implicit::__private::Content<'de>,
+            ) -> Result<Self, __E>
+            where __E: serde::de::Error
+            {
+               let __deserializer = serde_implicit::__private::ContentRefDeserializer::<__E>::new(__content);
 
+               #block
+
+               #body
+            }
         }
     })
 }
 
+/// The limits enforced while buffering, the thread's default overridden by
+/// those given on the enum.
+fn buffer_limits(limits: ast::Limits) -> TokenStream {
+    let fields = [
+        ("max_depth", limits.max_depth),
+        ("max_len", limits.max_len),
+        ("max_bytes", limits.max_bytes),
+    ]
+    .into_iter()
+    .filter_map(|(name, limit)| {
+        let name = format_ident!("{}", name);
+        let limit = Literal::usize_unsuffixed(limit?);
+        Some(quote! { #name: ::std::option::Option::Some(#limit), })
+    })
+    .collect::<Vec<_>>();
+
+    if fields.is_empty() {
+        quote! { serde_implicit::Limits::thread_default() }
+    } else {
+        quote! {
+            serde_implicit::Limits {
+                #(#fields)*
+                ..serde_implicit::Limits::thread_default()
+            }
+        }
+    }
+}
+
 pub fn enum_variant(enum_: &ast::Enum) -> proc_macro2::TokenStream {
     match &enum_.vars {
         Style::Tuple(_) => {
@@ -61,56 +192,101 @@ pub fn enum_variant(enum_: &ast::Enum) -> proc_macro2::TokenStream {
         Style::Struct {
             variants,
             fallthrough,
-        } => generate_variant_enum(&variants, fallthrough.as_ref()),
+        } => generate_variant_enum(variants, fallthrough, enum_.match_keys),
     }
 }
 
+/// How a struct enum reads its input.
+pub struct StructEnumOptions<'a> {
+    pub match_keys: MatchKeys,
+    pub accept_seq: bool,
+    /// Generate the body of `from_content_ref` rather than `deserialize`.
+    pub by_ref: bool,
+    /// The limits enforced while buffering, see `buffer_limits`.
+    pub limits: &'a TokenStream,
+}
+
 pub fn expand_struct_enum(
     ty_name: &Ident,
     generics: (ImplGenerics, TypeGenerics, Option<&WhereClause>),
     variants: &[ast::Variant],
-    fallthrough: Option<&Fallthrough>,
+    fallthrough: &[Fallthrough],
+    options: StructEnumOptions,
 ) -> syn::Result<proc_macro2::TokenStream> {
     let (impl_generics, ty_generics, where_clause) = generics;
+    let StructEnumOptions {
+        match_keys,
+        accept_seq,
+        by_ref,
+        limits,
+    } = options;
     let this_type_str = Literal::string(&ty_name.to_string());
 
+    // `from_content_ref` borrows the content it is given, `deserialize`
+    // owns what it buffered
+    let content_deserializer = if by_ref {
+        quote! { serde_implicit::__private::ContentRefDeserializer::<__E>::new(&__content) }
+    } else {
+        quote! { serde_implicit::__private::buffered_deserializer::<__D::Error>(__content) }
+    };
+
     let mut variant_arms = vec![];
     for (ix, var) in variants.iter().enumerate() {
-        let block = deserialize_fields(&var.fields);
+        let block = deserialize_fields(&var.fields, &var.field_opts, match_keys);
 
         let variant = implement_variant_deserializer(
-            &var.ident,
+            Some(&var.ident),
             &var.fields,
-            &ty_name,
+            &var.field_opts,
+            ty_name,
             &impl_generics,
             &ty_generics,
             &where_clause,
         );
         let cons = format_ident!("__variant{ix}");
         variant_arms.push(quote! {
-            __Variant::#cons => {#block #variant }
+            __Variant::#cons => {
+                let __deserializer = #content_deserializer;
+                #block
+                #variant
+            }
         });
     }
 
-    if let Some(fall) = &fallthrough {
-        let variant = implement_fallthrough_deserializer(&fall, &ty_name);
+    if !fallthrough.is_empty() {
+        let variant = implement_fallthrough_deserializer(fallthrough, ty_name, by_ref);
 
         variant_arms.push(quote! {
             __Variant::Fallthrough => { #variant }
         });
     }
 
-    let fallthrough = if fallthrough.is_some() {
+    let fallthrough = if !fallthrough.is_empty() {
         quote! { Some(__Variant::Fallthrough) }
     } else {
         quote! { None }
     };
 
+    let mut visitor = quote! {
+        serde_implicit::__private::TaggedContentVisitor::<__Variant>::new(#this_type_str, #fallthrough)
+    };
+    if accept_seq {
+        visitor = quote! { #visitor.accept_seq() };
+    }
+
+    let tagged = if by_ref {
+        quote! { #visitor.visit_content_ref::<__E>(__content)? }
+    } else {
+        quote! {
+            {
+                let __guard = serde_implicit::__private::BufferGuard::enter(#limits);
+                serde::Deserializer::deserialize_any(__deserializer, #visitor)?
+            }
+        }
+    };
+
     Ok(quote! {
-         let (__tag, __content) = serde::Deserializer::deserialize_any(
-            __deserializer,
-            serde_implicit::__private::TaggedContentVisitor::<__Variant>::new(#this_type_str, #fallthrough))?;
-        let __deserializer = serde_implicit::__private::ContentDeserializer::<__D::Error>::new(__content);
+        let (__tag, __content) = #tagged;
 
         match __tag {
             #(#variant_arms)*
@@ -120,11 +296,10 @@ pub fn expand_struct_enum(
 
 pub fn generate_variant_enum(
     variants: &[ast::Variant],
-    fallthrough: Option<&Fallthrough>,
+    fallthrough: &[Fallthrough],
+    match_keys: MatchKeys,
 ) -> TokenStream {
-    use proc_macro2::TokenStream;
     use quote::{format_ident, quote};
-    use std::str::FromStr;
 
     let variant_enum_variants = variants.iter().enumerate().map(|(i, _)| {
         let variant = format_ident!("__variant{}", i);
@@ -136,30 +311,40 @@ pub fn generate_variant_enum(
         #(#variant_enum_variants,)*
     };
 
-    let visit_str_arms = variants.iter().enumerate().map(|(i, var)| {
-        let tag_value = Literal::string(&var.tag.to_string());
-
+    let KeyMatch {
+        visit_str,
+        visit_bytes,
+        visit_u64,
+    } = match_key(
+        variants.iter().enumerate().map(|(i, var)| {
+            let variant = format_ident!("__variant{}", i);
+            (
+                var.tag.to_string(),
+                var.tag_key(),
+                quote! { ::std::result::Result::Ok(__Variant::#variant) },
+            )
+        }),
+        match_keys,
+        quote! { ::std::result::Result::Err(__E::missing_field("omg")) },
+    );
+
+    let allow_null_arms = variants.iter().enumerate().map(|(i, var)| {
         let variant = format_ident!("__variant{}", i);
+        let allow_null = var.allow_null;
         quote! {
-            #tag_value => ::std::result::Result::Ok(__Variant::#variant),
+            __Variant::#variant => #allow_null,
         }
     });
 
-    let visit_bytes_arms = variants.iter().enumerate().map(|(i, var)| {
-        let tag_value = &var.tag;
-
-        let byte_string = format!("b\"{}\"", tag_value);
-        let byte_tokens = TokenStream::from_str(&byte_string).unwrap_or_else(|_| {
-            quote! { #tag_value.as_bytes() }
-        });
-
+    let priority_arms = variants.iter().enumerate().map(|(i, var)| {
         let variant = format_ident!("__variant{}", i);
+        let priority = Literal::i32_unsuffixed(var.priority);
         quote! {
-            #byte_tokens => ::std::result::Result::Ok(__Variant::#variant),
+            __Variant::#variant => #priority,
         }
     });
 
-    let fallthrough_variant = fallthrough.map(|_| {
+    let fallthrough_variant = (!fallthrough.is_empty()).then(|| {
         quote! { Fallthrough }
     });
 
@@ -171,6 +356,25 @@ pub fn generate_variant_enum(
             #fallthrough_variant
         }
 
+        #[automatically_derived]
+        impl serde_implicit::__private::VariantTag for __Variant {
+            fn allow_null(&self) -> bool {
+                #[allow(unreachable_patterns)]
+                match self {
+                    #(#allow_null_arms)*
+                    _ => false,
+                }
+            }
+
+            fn priority(&self) -> i32 {
+                #[allow(unreachable_patterns)]
+                match self {
+                    #(#priority_arms)*
+                    _ => 0,
+                }
+            }
+        }
+
         #[doc(hidden)]
         struct __VariantVisitor;
 
@@ -195,11 +399,7 @@ pub fn generate_variant_enum(
             where
                 __E: serde::de::Error,
             {
-                match __value {
-                    #(#visit_str_arms)*
-                    _ => ::std::result::Result::Err(__E::missing_field("omg")),
-                    // _ => ::std::result::Result::Ok(__Variant::__ignore),
-                }
+                #visit_str
             }
 
             fn visit_bytes<__E>(
@@ -209,11 +409,17 @@ pub fn generate_variant_enum(
             where
                 __E: serde::de::Error,
             {
-                match __value {
-                    #(#visit_bytes_arms)*
-                    _ => ::std::result::Result::Err(__E::missing_field("omg")),
-                    // _ => ::std::result::Result::Ok(__Variant::__ignore),
-                }
+                #visit_bytes
+            }
+
+            fn visit_u64<__E>(
+                self,
+                __value: u64,
+            ) -> ::std::result::Result<Self::Value, __E>
+            where
+                __E: serde::de::Error,
+            {
+                #visit_u64
             }
         }
 
@@ -235,8 +441,109 @@ pub fn generate_variant_enum(
     }
 }
 
-fn deserialize_fields(fields: &ast::Fields) -> TokenStream {
-    let field_variants = (0..fields.named.len()).map(|i| {
+/// Bodies of the `visit_str`, `visit_bytes` and `visit_u64` methods of an
+/// identifier visitor.
+struct KeyMatch {
+    visit_str: TokenStream,
+    visit_bytes: TokenStream,
+    visit_u64: TokenStream,
+}
+
+/// Build an identifier visitor returning the expression paired with the
+/// first name `__value` matches under `match_keys`, or with its integer key,
+/// and `fallback` otherwise.
+fn match_key(
+    names: impl Iterator<Item = (String, Option<u64>, TokenStream)>,
+    match_keys: MatchKeys,
+    fallback: TokenStream,
+) -> KeyMatch {
+    let names: Vec<_> = names.collect();
+
+    let int_arms = names.iter().filter_map(|(_, key, value)| {
+        let key = Literal::u64_unsuffixed((*key)?);
+        Some(quote! { #key => #value, })
+    });
+    let visit_u64 = quote! {
+        match __value {
+            #(#int_arms)*
+            _ => #fallback,
+        }
+    };
+
+    let names = names.into_iter().map(|(name, _, value)| (name, value));
+    let eq = match match_keys {
+        MatchKeys::Exact => {
+            let (str_arms, bytes_arms): (Vec<_>, Vec<_>) = names
+                .map(|(name, value)| {
+                    let str_lit = Literal::string(&name);
+                    let bytes_lit = Literal::byte_string(name.as_bytes());
+                    (
+                        quote! { #str_lit => #value, },
+                        quote! { #bytes_lit => #value, },
+                    )
+                })
+                .unzip();
+
+            return KeyMatch {
+                visit_str: quote! {
+                    match __value {
+                        #(#str_arms)*
+                        _ => #fallback,
+                    }
+                },
+                visit_bytes: quote! {
+                    match __value {
+                        #(#bytes_arms)*
+                        _ => #fallback,
+                    }
+                },
+                visit_u64,
+            };
+        }
+        MatchKeys::CaseInsensitive => quote! { serde_implicit::__private::eq_case_insensitive },
+        MatchKeys::Normalized => quote! { serde_implicit::__private::eq_normalized },
+    };
+
+    let checks = names.map(|(name, value)| {
+        let folded = Literal::string(&match_keys.fold(&name));
+        quote! {
+            if #eq(__value, #folded) {
+                return #value;
+            }
+        }
+    });
+
+    KeyMatch {
+        visit_str: quote! {
+            #(#checks)*
+            #fallback
+        },
+        visit_bytes: quote! {
+            match ::std::str::from_utf8(__value) {
+                ::std::result::Result::Ok(__value) => serde::de::Visitor::visit_str(self, __value),
+                ::std::result::Result::Err(_) => #fallback,
+            }
+        },
+        visit_u64,
+    }
+}
+
+fn deserialize_fields(
+    fields: &ast::Fields,
+    field_opts: &[FieldOpts],
+    match_keys: MatchKeys,
+) -> TokenStream {
+    // Flattened fields have no key of their own
+    let keyed_fields = || {
+        fields
+            .named
+            .iter()
+            .zip(field_opts)
+            .enumerate()
+            .filter(|(_, (_, opts))| !opts.flatten)
+    };
+
+    let field_variants = keyed_fields().map(|(i, _)| {
         let variant = format_ident!("__field{}", i);
         quote! { #variant }
     });
@@ -247,25 +554,22 @@ fn deserialize_fields(fields: &ast::Fields) -> TokenStream {
         __ignore,
     };
 
-    let mut visit_str_arms = Vec::new();
-    let mut visit_bytes_arms = Vec::new();
-
-    for (i, field) in fields.named.iter().enumerate() {
-        let field_ident = field.ident.as_ref().unwrap();
-        let field_name = field_ident.to_string();
-        let variant = format_ident!("__field{}", i);
-
-        visit_str_arms.push(quote! {
-            #field_name => ::std::result::Result::Ok(__Field::#variant),
-        });
-
-        let byte_string = format!("b\"{}\"", field_name);
-        let byte_tokens = Literal::byte_string(&byte_string.as_bytes());
-
-        visit_bytes_arms.push(quote! {
-            #byte_tokens => ::std::result::Result::Ok(__Field::#variant),
-        });
-    }
+    let KeyMatch {
+        visit_str,
+        visit_bytes,
+        visit_u64,
+    } = match_key(
+        keyed_fields().map(|(i, (field, opts))| {
+            let variant = format_ident!("__field{}", i);
+            (
+                field.ident.as_ref().unwrap().to_string(),
+                opts.key,
+                quote! { ::std::result::Result::Ok(__Field::#variant) },
+            )
+        }),
+        match_keys,
+        quote! { ::std::result::Result::Ok(__Field::__ignore) },
+    );
 
     quote! {
         #[allow(non_camel_case_types)]
@@ -298,10 +602,7 @@ fn deserialize_fields(fields: &ast::Fields) -> TokenStream {
             where
                 __E: serde::de::Error,
             {
-                match __value {
-                    #(#visit_str_arms)*
-                    _ => ::std::result::Result::Ok(__Field::__ignore),
-                }
+                #visit_str
             }
 
             fn visit_bytes<__E>(
@@ -311,10 +612,17 @@ fn deserialize_fields(fields: &ast::Fields) -> TokenStream {
             where
                 __E: serde::de::Error,
             {
-                match __value {
-                    #(#visit_bytes_arms)*
-                    _ => ::std::result::Result::Ok(__Field::__ignore),
-                }
+                #visit_bytes
+            }
+
+            fn visit_u64<__E>(
+                self,
+                __value: u64,
+            ) -> ::std::result::Result<Self::Value, __E>
+            where
+                __E: serde::de::Error,
+            {
+                #visit_u64
             }
         }
 
@@ -337,20 +645,86 @@ fn deserialize_fields(fields: &ast::Fields) -> TokenStream {
 }
 
 fn implement_fallthrough_deserializer(
-    fallthrough: &Fallthrough,
+    fallthrough: &[Fallthrough],
     enum_name: &syn::Ident,
+    by_ref: bool,
 ) -> TokenStream {
-    let variant_name = &fallthrough.ident;
-    let field_name = &fallthrough.field.ident;
+    let (error, keys_of, ref_deserializer) = if by_ref {
+        (
+            quote! { __E },
+            quote! { serde_implicit::__private::fallthrough_keys },
+            quote! { serde_implicit::__private::ContentRefDeserializer::<__E>::new(&__content) },
+        )
+    } else {
+        (
+            quote! { __D::Error },
+            quote! { serde_implicit::__private::buffered_keys },
+            quote! { serde_implicit::__private::buffered_ref_deserializer::<__D::Error>(&__content) },
+        )
+    };
+
+    let construct = |fallthrough: &Fallthrough, deserializer: TokenStream| {
+        let variant_name = &fallthrough.ident;
+        let keys = fallthrough.keys.as_ref().map(|keys| {
+            quote! {
+                let #keys = #keys_of(&__content)
+                    .map(::std::string::String::from)
+                    .collect();
+            }
+        });
+        let keys_field = fallthrough.keys.as_ref().map(|keys| quote! { #keys, });
+
+        let value = match &fallthrough.field.ident {
+            Some(field_name) => {
+                quote! { #enum_name :: #variant_name { #field_name: __value, #keys_field } }
+            }
+            None => quote! { #enum_name :: #variant_name(__value) },
+        };
+
+        quote! {
+            {
+                #keys
+                serde::Deserialize::deserialize(#deserializer).map(|__value| #value)
+            }
+        }
+    };
+
+    // A single fallthrough takes the buffered content by value, reporting
+    // its own error.
+    if let [single] = fallthrough {
+        let deserializer = if by_ref {
+            quote! { serde_implicit::__private::ContentRefDeserializer::<__E>::new(&__content) }
+        } else {
+            quote! { serde_implicit::__private::buffered_deserializer::<__D::Error>(__content) }
+        };
+        let construct = construct(single, deserializer);
+        return quote! { #construct };
+    }
+
+    // Several are tried in order against the content, like untagged variants.
+    let ty_name_str = Literal::string(&enum_name.to_string());
+    let trials = fallthrough.iter().map(|fall| {
+        let variant_str = Literal::string(&fall.ident.to_string());
+        let construct = construct(fall, ref_deserializer.clone());
+        quote! {
+            match #construct {
+                ::std::result::Result::Ok(__value) => return ::std::result::Result::Ok(__value),
+                ::std::result::Result::Err(__err) => __errors.whole(#variant_str, __err),
+            }
+        }
+    });
 
     quote! {
-        serde::Deserialize::deserialize(__deserializer).map(|res| { #enum_name :: #variant_name { #field_name: res } })
+        let mut __errors = serde_implicit::__private::TrialErrors::<#error>::new(#ty_name_str);
+        #(#trials)*
+        ::std::result::Result::Err(__errors.finish_whole())
     }
 }
 
 fn implement_variant_deserializer(
-    variant_ident: &Ident,
+    variant_ident: Option<&Ident>,
     fields: &ast::Fields,
+    field_opts: &[FieldOpts],
     enum_name: &syn::Ident,
     impl_generics: &ImplGenerics,
     ty_generics: &TypeGenerics,
@@ -358,26 +732,48 @@ fn implement_variant_deserializer(
 ) -> TokenStream {
     use quote::{format_ident, quote};
 
-    let variant_name = format!("{}::{}", enum_name, variant_ident);
-    let expecting_message = format!("struct variant {}", variant_name);
+    let expecting_message = match variant_ident {
+        Some(variant_ident) => format!("struct variant {}::{}", enum_name, variant_ident),
+        None => format!("struct {}", enum_name),
+    };
+
+    let has_flatten = field_opts.iter().any(|opts| opts.flatten);
 
     let mut field_declarations = Vec::new();
     let mut field_processing = Vec::new();
     let mut final_fields = Vec::new();
+    let mut flatten_fields = Vec::new();
 
-    for (i, field) in fields.named.iter().enumerate() {
+    for (i, (field, opts)) in fields.named.iter().zip(field_opts).enumerate() {
         let field_ident = field.ident.as_ref().unwrap();
         let field_name = field_ident.to_string();
         let field_type = &field.ty;
         let field_var = format_ident!("__field{}", i);
         let field_enum_variant = format_ident!("__field{}", i);
 
+        if opts.flatten {
+            // Deserialized from the leftover entries once the map is done
+            flatten_fields.push(quote! {
+                let #field_var: #field_type = serde::Deserialize::deserialize(
+                    serde_implicit::__private::FlatMapDeserializer::<__A::Error>::new(&mut __collect),
+                )?;
+            });
+            continue;
+        }
+
+        // With flattened fields the key is buffered to be kept if unclaimed
+        let pattern = if has_flatten {
+            quote! { ::std::option::Option::Some(__Field::#field_enum_variant) }
+        } else {
+            quote! { __Field::#field_enum_variant }
+        };
+
         field_declarations.push(quote! {
             let mut #field_var: ::std::option::Option<#field_type> = ::std::option::Option::None;
         });
 
         field_processing.push(quote! {
-            __Field::#field_enum_variant => {
+            #pattern => {
                 if ::std::option::Option::is_some(&#field_var) {
                     return ::std::result::Result::Err(
                         <__A::Error as serde::de::Error>::duplicate_field(#field_name),
@@ -399,18 +795,68 @@ fn implement_variant_deserializer(
         });
     }
 
+    let read_map = if has_flatten {
+        quote! {
+            let mut __collect = ::std::vec::Vec::<
+                ::std::option::Option<(
+                    serde_implicit::__private::Content<'de>,
+                    serde_implicit::__private::Content<'de>,
+                )>,
+            >::new();
+
+            while let ::std::option::Option::Some(__key) = serde::de::MapAccess::next_key::<
+                serde_implicit::__private::Content<'de>,
+            >(&mut __map)? {
+                let __field = <__Field as serde::Deserialize>::deserialize(
+                    serde_implicit::__private::ContentRefDeserializer::<__A::Error>::new(&__key),
+                );
+                match ::std::result::Result::ok(__field) {
+                    #(#field_processing)*
+                    _ => {
+                        let __value = serde::de::MapAccess::next_value::<
+                            serde_implicit::__private::Content<'de>,
+                        >(&mut __map)?;
+                        __collect.push(::std::option::Option::Some((__key, __value)));
+                    }
+                }
+            }
+        }
+    } else {
+        quote! {
+            while let ::std::option::Option::Some(__key) = serde::de::MapAccess::next_key::<
+                __Field,
+            >(&mut __map)? {
+                match __key {
+                    #(#field_processing)*
+                    _ => {
+                        let _ = serde::de::MapAccess::next_value::<
+                            serde::de::IgnoredAny,
+                        >(&mut __map)?;
+                    }
+                }
+            }
+        }
+    };
+
+    // Checked while the fields are still optional
+    let requires = requires_checks(fields, field_opts);
+
     let field_idents = fields.named.iter().map(|f| f.ident.as_ref().unwrap());
     let field_vars = (0..fields.named.len()).map(|i| format_ident!("__field{}", i));
 
+    let path = match variant_ident {
+        Some(variant_ident) => quote! { #enum_name::#variant_ident },
+        None => quote! { #enum_name },
+    };
     let struct_init = quote! {
-        #enum_name::#variant_ident {
+        #path {
             #(#field_idents: #field_vars),*
         }
     };
 
     quote! {
         #[doc(hidden)]
-        struct __Visitor<'de, #ty_generics> {
+        struct __Visitor<'de, #impl_generics> #where_clause {
             marker: ::std::marker::PhantomData<#enum_name < #ty_generics >>,
             lifetime: ::std::marker::PhantomData<&'de ()>,
         }
@@ -439,20 +885,12 @@ fn implement_variant_deserializer(
             {
                 #(#field_declarations)*
 
-                while let ::std::option::Option::Some(__key) = serde::de::MapAccess::next_key::<
-                    __Field,
-                >(&mut __map)? {
-                    match __key {
-                        #(#field_processing)*
-                        _ => {
-                            let _ = serde::de::MapAccess::next_value::<
-                                serde::de::IgnoredAny,
-                            >(&mut __map)?;
-                        }
-                    }
-                }
+                #read_map
+
+                #(#requires)*
 
                 #(#final_fields)*
+                #(#flatten_fields)*
 
                 ::std::result::Result::Ok(#struct_init)
             }
@@ -469,6 +907,44 @@ fn implement_variant_deserializer(
     }
 }
 
+/// Errors for fields present without the fields they `require`. An `Option`
+/// field holding `null` counts as absent.
+fn requires_checks(fields: &ast::Fields, field_opts: &[FieldOpts]) -> Vec<TokenStream> {
+    let present = |i: usize| {
+        let field_var = format_ident!("__field{}", i);
+        if ast::is_option(&fields.named[i].ty) {
+            quote! { ::std::matches!(#field_var, ::std::option::Option::Some(::std::option::Option::Some(_))) }
+        } else {
+            quote! { ::std::option::Option::is_some(&#field_var) }
+        }
+    };
+    let index_of = |name: &Ident| {
+        fields
+            .named
+            .iter()
+            .position(|f| f.ident.as_ref() == Some(name))
+            .unwrap()
+    };
+
+    let mut checks = vec![];
+    for (i, (field, opts)) in fields.named.iter().zip(field_opts).enumerate() {
+        let field_name = field.ident.as_ref().unwrap();
+        for name in &opts.requires {
+            let is_present = present(i);
+            let other_present = present(index_of(name));
+            let message = Literal::string(&format!("field `{field_name}` requires `{name}`"));
+            checks.push(quote! {
+                if #is_present && !(#other_present) {
+                    return ::std::result::Result::Err(
+                        <__A::Error as serde::de::Error>::custom(#message),
+                    );
+                }
+            });
+        }
+    }
+    checks
+}
+
 mod annoying {
     use proc_macro2::TokenStream;
     use quote::{ToTokens, quote};
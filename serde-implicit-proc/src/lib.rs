@@ -12,18 +12,21 @@ mod tuple_enum;
 /// commits to the corresponding variant and produces targeted error messages
 /// instead of serde's generic "data did not match any variant" error.
 ///
-/// **Tag fields should be non-optional.** During deserialization, keys whose
-/// value is `null` are ignored when searching for the implicit tag. If a tag
-/// field is `Option<T>` and the input contains `"field": null`, that variant
-/// will not be selected.
+/// **Tag fields are non-optional.** During deserialization, keys whose value
+/// is `null` are ignored when searching for the implicit tag, so an
+/// `Option<T>` tag field is rejected unless it is marked
+/// `#[serde_implicit(tag, allow_null)]`, which selects the variant whenever
+/// the key is present.
+///
+/// Structs with named fields can derive it too. They have no tag, but a field
+/// marked `#[serde_implicit(requires = "a, b")]` makes the listed fields
+/// mandatory whenever it is present, with an error naming both.
 // todo: shadow serde completely?
-#[proc_macro_derive(Deserialize, attributes(serde_implicit))]
+#[proc_macro_derive(Deserialize, attributes(serde_implicit, serde))]
 pub fn derive_serialize(input: TS1) -> TS1 {
     let input = parse_macro_input!(input as DeriveInput);
 
-    let ts = expand::expand_derive_serialize(input)
+    expand::expand_derive_serialize(input)
         .unwrap_or_else(syn::Error::into_compile_error)
-        .into();
-
-    ts
+        .into()
 }
@@ -1,10 +1,14 @@
 use quote::{format_ident, quote};
-use syn::Ident;
+use syn::{Ident, WhereClause};
 
-use crate::ast::{self};
+use crate::{
+    ast::{self},
+    expand::{ImplGenerics, TypeGenerics},
+};
 
 pub fn expand_tuple_enum(
     ty_name: &Ident,
+    generics: (ImplGenerics, TypeGenerics, Option<&WhereClause>),
     variants: &[ast::TupleVariant],
 ) -> syn::Result<proc_macro2::TokenStream> {
     // Separate variants into regular and flatten groups
@@ -25,42 +29,105 @@ pub fn expand_tuple_enum(
             .iter()
             .nth(tag_index)
             .expect("tag index must be smaller than variant's field count");
-        let tag_type = &tag_field.ty;
+        let tag_type = v.tag_type.as_ref().unwrap_or(&tag_field.ty);
 
-        let trial = if field_count == 1 {
+        let variant_str = proc_macro2::Literal::string(&variant_ident.to_string());
+        let tag_path_str = proc_macro2::Literal::string(
+            &std::iter::once(tag_index)
+                .chain(v.tag_path.iter().copied())
+                .map(|ix| ix.to_string())
+                .collect::<Vec<_>>()
+                .join("."),
+        );
+
+        let trial = if field_count == 1 && v.tag_path.is_empty() && v.tag_with.is_none() {
             quote! {
-                if let serde_implicit::__private::Content::Seq(ref __seq) = __content {
+                if let serde_implicit::__private::Content::Seq(ref __seq) = *__content {
                     if __seq.len() == 1 {
-                        if let ::std::result::Result::Ok(__tag) = <#tag_type as serde::Deserialize>::deserialize(
-                            serde_implicit::__private::ContentRefDeserializer::<__D::Error>::new(&__seq[0])
+                        match <#tag_type as serde::Deserialize>::deserialize(
+                            serde_implicit::__private::ContentRefDeserializer::<__E>::new(&__seq[0])
                         ) {
-                            return ::std::result::Result::Ok(#ty_name::#variant_ident(__tag));
+                            ::std::result::Result::Ok(__tag) => {
+                                return ::std::result::Result::Ok(#ty_name::#variant_ident(__tag));
+                            }
+                            ::std::result::Result::Err(__err) => __errors.tag(#variant_str, #tag_path_str, __err),
                         }
+                    } else {
+                        __errors.length(#variant_str, 1);
                     }
                 } else {
                     // Try to deserialize the entire content as the tag
-                    if let ::std::result::Result::Ok(__tag) = <#tag_type as serde::Deserialize>::deserialize(
-                        serde_implicit::__private::ContentDeserializer::<__D::Error>::new(__content.clone())
+                    match <#tag_type as serde::Deserialize>::deserialize(
+                        serde_implicit::__private::ContentRefDeserializer::<__E>::new(__content)
                     ) {
-                        return ::std::result::Result::Ok(#ty_name::#variant_ident(__tag));
+                        ::std::result::Result::Ok(__tag) => {
+                            return ::std::result::Result::Ok(#ty_name::#variant_ident(__tag));
+                        }
+                        ::std::result::Result::Err(__err) => __errors.whole(#variant_str, __err),
+                    }
+                }
+            }
+        } else if field_count == 1 {
+            // The tag is checked separately from the only field (it is nested
+            // or decided by a predicate): once it matches, commit and
+            // deserialize the whole field.
+            let field_type = &tag_field.ty;
+            let error_context = format!("{}::{}: {{}}", ty_name, variant_ident);
+            let on_match = quote! {
+                return <#field_type as serde::Deserialize>::deserialize(
+                    serde_implicit::__private::ContentRefDeserializer::<__E>::new(__field)
+                )
+                .map(#ty_name::#variant_ident)
+                .map_err(|__e| serde::de::Error::custom(format!(#error_context, __e)));
+            };
+            let check = tag_check(
+                v,
+                tag_type,
+                quote! { __field },
+                &variant_str,
+                &tag_path_str,
+                on_match,
+            );
+
+            quote! {
+                match *__content {
+                    serde_implicit::__private::Content::Seq(ref __seq) if __seq.len() != 1 => {
+                        __errors.length(#variant_str, 1);
+                    }
+                    _ => {
+                        let __field = match *__content {
+                            serde_implicit::__private::Content::Seq(ref __seq) => &__seq[0],
+                            ref __field => __field,
+                        };
+                        #check
                     }
                 }
             }
         } else {
             let variant_deserializer =
-                implement_variant_deserializer(variant_ident, fields, ty_name);
+                implement_variant_deserializer(variant_ident, fields, ty_name, generics);
             let tag_index_lit = proc_macro2::Literal::usize_unsuffixed(tag_index);
             let field_count_lit = proc_macro2::Literal::usize_unsuffixed(field_count);
+            let on_match = quote! {
+                let __deserializer = serde_implicit::__private::ContentRefDeserializer::<__E>::new(__content);
+                return #variant_deserializer;
+            };
+            let check = tag_check(
+                v,
+                tag_type,
+                quote! { &__seq[#tag_index_lit] },
+                &variant_str,
+                &tag_path_str,
+                on_match,
+            );
 
             quote! {
-                if let serde_implicit::__private::Content::Seq(ref __seq) = __content {
-                    // Check length and tag, if both pass, commit to this variant
-                    if __seq.len() == #field_count_lit && <#tag_type as serde::Deserialize>::deserialize(
-                        serde_implicit::__private::ContentRefDeserializer::<__D::Error>::new(&__seq[#tag_index_lit])
-                    ).is_ok() {
-                        let __deserializer = serde_implicit::__private::ContentRefDeserializer::<__D::Error>::new(&__content);
-                        return #variant_deserializer;
+                match *__content {
+                    serde_implicit::__private::Content::Seq(ref __seq) if __seq.len() == #field_count_lit => {
+                        // Length matches, if the tag parses commit to this variant
+                        #check
                     }
+                    _ => __errors.length(#variant_str, #field_count_lit),
                 }
             }
         };
@@ -80,23 +147,27 @@ pub fn expand_tuple_enum(
         })?;
         let field_type = &field.ty;
 
+        let variant_str = proc_macro2::Literal::string(&variant_ident.to_string());
+
         let trial = quote! {
-            if let ::std::result::Result::Ok(__field0) = <#field_type as serde::Deserialize>::deserialize(
-                serde_implicit::__private::ContentDeserializer::<__D::Error>::new(__content.clone())
+            match <#field_type as serde::Deserialize>::deserialize(
+                serde_implicit::__private::ContentRefDeserializer::<__E>::new(__content)
             ) {
-                return ::std::result::Result::Ok(#ty_name::#variant_ident(__field0));
+                ::std::result::Result::Ok(__field0) => {
+                    return ::std::result::Result::Ok(#ty_name::#variant_ident(__field0));
+                }
+                ::std::result::Result::Err(__err) => __errors.whole(#variant_str, __err),
             }
         };
 
         flatten_trials.push(trial);
     }
 
-    let expected_str = proc_macro2::Literal::string(&format!("a valid variant of {}", ty_name));
+    let ty_name_str = proc_macro2::Literal::string(&ty_name.to_string());
 
     Ok(quote! {
-        let __content = <serde_implicit::__private::Content as serde::Deserialize>::deserialize(
-            __deserializer,
-        )?;
+        #[allow(unused_mut)]
+        let mut __errors = serde_implicit::__private::TrialErrors::<__E>::new(#ty_name_str);
 
         // Try each regular variant in order
         #(#variant_trials)*
@@ -104,18 +175,69 @@ pub fn expand_tuple_enum(
         // If no regular variant matched, try flatten variants
         #(#flatten_trials)*
 
-        // No variant matched
-        ::std::result::Result::Err(serde::de::Error::custom(format!(
-            "data did not match any variant of enum {}",
-            #expected_str
-        )))
+        // No variant matched, report why each one was rejected
+        ::std::result::Result::Err(__errors.finish(__content))
     })
 }
 
+/// Check the tag found by following the variant's tag path from
+/// `field_content`, running `on_match` if it deserializes as `tag_type` (or
+/// satisfies the `tag_with` predicate) and recording the rejection otherwise.
+fn tag_check(
+    v: &ast::TupleVariant,
+    tag_type: &syn::Type,
+    field_content: proc_macro2::TokenStream,
+    variant_str: &proc_macro2::Literal,
+    tag_path_str: &proc_macro2::Literal,
+    on_match: proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    let check = |tag_content: proc_macro2::TokenStream| match &v.tag_with {
+        Some(predicate) => {
+            let predicate_str =
+                proc_macro2::Literal::string(&quote!(#predicate).to_string().replace(' ', ""));
+            quote! {
+                if #predicate(#tag_content) {
+                    #on_match
+                } else {
+                    __errors.predicate(#variant_str, #tag_path_str, #predicate_str);
+                }
+            }
+        }
+        None => quote! {
+            match <#tag_type as serde::Deserialize>::deserialize(
+                serde_implicit::__private::ContentRefDeserializer::<__E>::new(#tag_content)
+            ) {
+                ::std::result::Result::Ok(_) => {
+                    #on_match
+                }
+                ::std::result::Result::Err(__err) => __errors.tag(#variant_str, #tag_path_str, __err),
+            }
+        },
+    };
+
+    if v.tag_path.is_empty() {
+        return check(field_content);
+    }
+
+    let tag_path = v
+        .tag_path
+        .iter()
+        .map(|ix| proc_macro2::Literal::usize_unsuffixed(*ix));
+    let check = check(quote! { __tag_content });
+
+    quote! {
+        match serde_implicit::__private::content_at_path(#field_content, &[#(#tag_path),*]) {
+            ::std::option::Option::Some(__tag_content) => { #check }
+            ::std::option::Option::None => __errors.missing_tag(#variant_str, #tag_path_str),
+        }
+    }
+}
+
 fn implement_variant_deserializer(
     variant_ident: &Ident,
     fields: &syn::FieldsUnnamed,
     enum_name: &syn::Ident,
+    (impl_generics, ty_generics, where_clause): (ImplGenerics, TypeGenerics, Option<&WhereClause>),
 ) -> proc_macro2::TokenStream {
     let variant_name = format!("{}::{}", enum_name, variant_ident);
     let expecting_message = format!("tuple variant {}", variant_name);
@@ -159,11 +281,14 @@ fn implement_variant_deserializer(
     quote! {
         {
             #[doc(hidden)]
-            struct __Visitor;
+            struct __Visitor<'de, #impl_generics> #where_clause {
+                marker: ::std::marker::PhantomData<#enum_name < #ty_generics >>,
+                lifetime: ::std::marker::PhantomData<&'de ()>,
+            }
 
             #[automatically_derived]
-            impl<'de> serde::de::Visitor<'de> for __Visitor {
-                type Value = #enum_name;
+            impl<'de, #impl_generics> serde::de::Visitor<'de> for __Visitor<'de, #ty_generics> #where_clause {
+                type Value = #enum_name < #ty_generics >;
 
                 fn expecting(
                     &self,
@@ -188,7 +313,10 @@ fn implement_variant_deserializer(
 
             serde::Deserializer::deserialize_seq(
                 __deserializer,
-                __Visitor,
+                __Visitor {
+                    marker: ::std::marker::PhantomData::<#enum_name < #ty_generics >>,
+                    lifetime: ::std::marker::PhantomData,
+                },
             )
         }
     }
@@ -0,0 +1,97 @@
+use criterion::{BenchmarkId, Criterion, black_box, criterion_group, criterion_main};
+use serde::Deserialize;
+use serde_implicit::__private::ContentRefDeserializer;
+use serde_implicit::content::Content;
+#[cfg(feature = "tape")]
+use serde_implicit::tape::Tape;
+use serde_json::json;
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+struct Record {
+    id: u64,
+    name: String,
+    tags: Vec<String>,
+    location: Location,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+struct Location {
+    lat: f64,
+    lon: f64,
+}
+
+/// A batch of `len` records, with escapes in the strings so that they are
+/// copied out of the input rather than borrowed.
+fn input(len: u64) -> String {
+    let records: Vec<_> = (0..len)
+        .map(|id| {
+            json!({
+                "id": id,
+                "name": format!("record \"{id}\""),
+                "tags": ["a\tb", "c\td", "e\tf"],
+                "location": { "lat": 1.5, "lon": -2.5 },
+            })
+        })
+        .collect();
+    json!({ "batch": 1, "records": records }).to_string()
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+struct Batch {
+    batch: u64,
+    records: Vec<Record>,
+}
+
+#[allow(dead_code)]
+#[derive(serde_implicit::Deserialize, Debug)]
+enum Message {
+    Batch {
+        #[serde_implicit(tag)]
+        batch: u64,
+        records: Vec<Record>,
+    },
+}
+
+/// Buffer the input and replay it, as `Content` and, with the `tape` feature,
+/// as a `Tape`. The derived enum uses whichever the feature selects, so run
+/// this with and without it to compare the two end to end.
+fn buffering(c: &mut Criterion) {
+    let mut group = c.benchmark_group("buffering");
+    let derive = if cfg!(feature = "tape") {
+        "derive_tape"
+    } else {
+        "derive_content"
+    };
+
+    for len in [10, 100, 1_000] {
+        let input = input(len);
+
+        group.bench_with_input(BenchmarkId::new("content", len), &input, |b, input| {
+            b.iter(|| {
+                let content: Content = serde_json::from_str(black_box(input)).unwrap();
+                Batch::deserialize(ContentRefDeserializer::<serde_json::Error>::new(&content))
+                    .unwrap()
+            })
+        });
+
+        #[cfg(feature = "tape")]
+        group.bench_with_input(BenchmarkId::new("tape", len), &input, |b, input| {
+            b.iter(|| {
+                let tape: Tape = serde_json::from_str(black_box(input)).unwrap();
+                Batch::deserialize(tape.deserializer::<serde_json::Error>()).unwrap()
+            })
+        });
+
+        group.bench_with_input(BenchmarkId::new(derive, len), &input, |b, input| {
+            b.iter(|| serde_json::from_str::<Message>(black_box(input)).unwrap())
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, buffering);
+criterion_main!(benches);
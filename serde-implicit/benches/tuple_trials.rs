@@ -0,0 +1,65 @@
+use criterion::{BenchmarkId, Criterion, black_box, criterion_group, criterion_main};
+use serde::de::{Deserialize, Deserializer, Error};
+use serde_json::{Value, json};
+
+/// Only deserializes from the integer `N`, so each variant rejects the input
+/// as soon as it sees the first element.
+#[derive(Debug)]
+struct Tag<const N: u64>;
+
+impl<'de, const N: u64> Deserialize<'de> for Tag<N> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let n = u64::deserialize(deserializer)?;
+        if n == N {
+            Ok(Tag)
+        } else {
+            Err(D::Error::custom(format_args!(
+                "expected tag {N}, found {n}"
+            )))
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(serde::Deserialize, Debug)]
+struct Payload<const N: u64>(Tag<N>, Vec<u64>);
+
+macro_rules! wide_enum {
+    ($($var:ident = $n:literal),* $(,)?) => {
+        /// Twenty flatten variants, each tried against the whole input in turn.
+        #[allow(dead_code)]
+        #[derive(serde_implicit::Deserialize, Debug)]
+        enum Wide {
+            $($var(#[serde_implicit(flatten)] Payload<$n>),)*
+        }
+    };
+}
+
+wide_enum! {
+    V0 = 0, V1 = 1, V2 = 2, V3 = 3, V4 = 4, V5 = 5, V6 = 6, V7 = 7, V8 = 8, V9 = 9,
+    V10 = 10, V11 = 11, V12 = 12, V13 = 13, V14 = 14, V15 = 15, V16 = 16, V17 = 17, V18 = 18,
+    V19 = 19,
+}
+
+fn input(len: u64) -> Value {
+    json!([19, (0..len).collect::<Vec<_>>()])
+}
+
+fn tuple_trials(c: &mut Criterion) {
+    let mut group = c.benchmark_group("tuple_trials");
+
+    for len in [1_000, 10_000, 100_000] {
+        let value = input(len);
+        group.bench_with_input(BenchmarkId::new("last_of_20", len), &value, |b, value| {
+            b.iter(|| Wide::deserialize(black_box(value)).unwrap())
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, tuple_trials);
+criterion_main!(benches);
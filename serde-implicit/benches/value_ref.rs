@@ -0,0 +1,64 @@
+use criterion::{BenchmarkId, Criterion, black_box, criterion_group, criterion_main};
+use serde_json::{Value, json};
+
+#[allow(dead_code)]
+#[derive(serde::Deserialize, Debug)]
+struct Record {
+    id: u64,
+    name: String,
+    tags: Vec<String>,
+}
+
+#[allow(dead_code)]
+#[derive(serde_implicit::Deserialize, Debug)]
+enum Message {
+    Batch {
+        #[serde_implicit(tag)]
+        batch: u64,
+        records: Vec<Record>,
+    },
+}
+
+fn input(len: u64) -> Value {
+    let records: Vec<_> = (0..len)
+        .map(|id| {
+            json!({
+                "id": id,
+                "name": format!("record {id}"),
+                "tags": ["a", "b", "c"],
+            })
+        })
+        .collect();
+    json!({ "batch": 1, "records": records })
+}
+
+/// `from_value_ref` still builds a `Content` tree borrowing the strings of
+/// the value; compare it to cloning the value for `serde_json::from_value`.
+fn value_ref(c: &mut Criterion) {
+    let mut group = c.benchmark_group("value_ref");
+
+    for len in [10, 100, 1_000] {
+        let input = input(len);
+
+        group.bench_with_input(
+            BenchmarkId::new("from_value_ref", len),
+            &input,
+            |b, input| {
+                b.iter(|| serde_implicit::from_value_ref::<Message>(black_box(input)).unwrap())
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("from_value_clone", len),
+            &input,
+            |b, input| {
+                b.iter(|| serde_json::from_value::<Message>(black_box(input).clone()).unwrap())
+            },
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, value_ref);
+criterion_main!(benches);
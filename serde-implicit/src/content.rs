@@ -1,27 +1,39 @@
-// This module is private and nothing here should be used outside of
-// generated code.
+//! The buffered representation of a value, as seen by implicit tags.
+//!
+//! [`Content`] is public API: it is what `tag_with` predicates and
+//! [`FromContentRef`](crate::FromContentRef) receive, and can be built,
+//! inspected and serialized on its own. The deserializers and visitors in
+//! this module are used from generated code and may change without notice.
 //
-// We will iterate on the implementation for a few releases and only have to
-// worry about backward compatibility for the `untagged` and `tag` attributes
-// rather than for this entire mechanism.
-//
-// This issue is tracking making some of this stuff public:
+// Adapted from serde's private `Content`, see
 // https://github.com/serde-rs/serde/issues/741
 
 use std::fmt;
 use std::marker::PhantomData;
 
+use serde::Serialize;
+
 use serde::de::value::{MapDeserializer, SeqDeserializer};
 use serde::de::{
     self, Deserialize, DeserializeSeed, Deserializer, EnumAccess, Expected, IgnoredAny, MapAccess,
     SeqAccess, Unexpected, Visitor,
 };
 
-/// Used from generated code to buffer the contents of the Deserializer when
-/// deserializing untagged enums and internally tagged enums.
+use crate::limits;
+
+/// The private keys `serde_json` passes arbitrary precision numbers and raw
+/// values under, as maps with a single entry holding their text.
+pub(crate) const NUMBER_TOKEN: &str = "$serde_json::private::Number";
+pub(crate) const RAW_VALUE_TOKEN: &str = "$serde_json::private::RawValue";
+
+/// Any value of the serde data model, buffered from a `Deserializer`.
 ///
-/// Not public API. Use serde-value instead.
+/// Strings and bytes are borrowed from the input when the format allows it,
+/// see [`Content::into_owned`] to detach them. Borrowed and owned strings (or
+/// bytes) compare equal, other variants only equal themselves, so `U8(1)` is
+/// not `U64(1)`. Maps compare entry by entry, in order.
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub enum Content<'de> {
     Bool(bool),
 
@@ -29,11 +41,13 @@ pub enum Content<'de> {
     U16(u16),
     U32(u32),
     U64(u64),
+    U128(u128),
 
     I8(i8),
     I16(i16),
     I32(i32),
     I64(i64),
+    I128(i128),
 
     F32(f32),
     F64(f64),
@@ -54,6 +68,7 @@ pub enum Content<'de> {
 }
 
 impl<'de> Content<'de> {
+    /// The string, or bytes holding valid UTF-8.
     pub fn as_str(&self) -> Option<&str> {
         match *self {
             Content::Str(x) => Some(x),
@@ -64,18 +79,193 @@ impl<'de> Content<'de> {
         }
     }
 
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match *self {
+            Content::Str(x) => Some(x.as_bytes()),
+            Content::String(ref x) => Some(x.as_bytes()),
+            Content::Bytes(x) => Some(x),
+            Content::ByteBuf(ref x) => Some(x),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            Content::Bool(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    /// Any integer that fits in a `u64`.
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            Content::U8(n) => Some(n as u64),
+            Content::U16(n) => Some(n as u64),
+            Content::U32(n) => Some(n as u64),
+            Content::U64(n) => Some(n),
+            Content::U128(n) => u64::try_from(n).ok(),
+            Content::I8(n) => u64::try_from(n).ok(),
+            Content::I16(n) => u64::try_from(n).ok(),
+            Content::I32(n) => u64::try_from(n).ok(),
+            Content::I64(n) => u64::try_from(n).ok(),
+            Content::I128(n) => u64::try_from(n).ok(),
+            _ => self.as_number_str()?.parse().ok(),
+        }
+    }
+
+    /// Any integer that fits in an `i64`.
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            Content::U8(n) => Some(n as i64),
+            Content::U16(n) => Some(n as i64),
+            Content::U32(n) => Some(n as i64),
+            Content::U64(n) => i64::try_from(n).ok(),
+            Content::U128(n) => i64::try_from(n).ok(),
+            Content::I8(n) => Some(n as i64),
+            Content::I16(n) => Some(n as i64),
+            Content::I32(n) => Some(n as i64),
+            Content::I64(n) => Some(n),
+            Content::I128(n) => i64::try_from(n).ok(),
+            _ => self.as_number_str()?.parse().ok(),
+        }
+    }
+
+    /// Any integer that fits in a `u128`.
+    pub fn as_u128(&self) -> Option<u128> {
+        match *self {
+            Content::U128(n) => Some(n),
+            Content::I128(n) => u128::try_from(n).ok(),
+            Content::Map(_) => self.as_number_str()?.parse().ok(),
+            _ => self.as_u64().map(u128::from),
+        }
+    }
+
+    /// Any integer that fits in an `i128`.
+    pub fn as_i128(&self) -> Option<i128> {
+        match *self {
+            Content::U128(n) => i128::try_from(n).ok(),
+            Content::I128(n) => Some(n),
+            Content::Map(_) => self.as_number_str()?.parse().ok(),
+            _ => self
+                .as_i64()
+                .map(i128::from)
+                .or_else(|| self.as_u64().map(i128::from)),
+        }
+    }
+
+    /// Any number, possibly losing precision for large integers.
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Content::F32(f) => Some(f as f64),
+            Content::F64(f) => Some(f),
+            Content::U128(n) => Some(n as f64),
+            Content::I128(n) => Some(n as f64),
+            Content::Map(_) => self.as_number_str()?.parse().ok(),
+            _ => self
+                .as_i64()
+                .map(|n| n as f64)
+                .or_else(|| self.as_u64().map(|n| n as f64)),
+        }
+    }
+
+    pub fn as_seq(&self) -> Option<&[Content<'de>]> {
+        match *self {
+            Content::Seq(ref items) => Some(items),
+            _ => None,
+        }
+    }
+
+    /// The entries of a map, in input order.
+    pub fn as_map(&self) -> Option<&[(Content<'de>, Content<'de>)]> {
+        match *self {
+            Content::Map(ref entries) => Some(entries),
+            _ => None,
+        }
+    }
+
+    /// The value of the first entry of a map whose key is the string `key`.
+    pub fn get(&self, key: &str) -> Option<&Content<'de>> {
+        self.as_map()?
+            .iter()
+            .find(|(k, _)| k.as_str() == Some(key))
+            .map(|(_, v)| v)
+    }
+
+    /// Whether this is `None` or `Unit`, how formats spell `null`.
+    pub fn is_null(&self) -> bool {
+        matches!(self, Content::None | Content::Unit)
+    }
+
+    /// The digits of a number `serde_json` buffered with its
+    /// `arbitrary_precision` feature, which it passes as a map with a single
+    /// entry under a private key.
+    pub(crate) fn as_number_str(&self) -> Option<&str> {
+        self.private_entry(NUMBER_TOKEN)?.as_str()
+    }
+
+    /// The value of a map with a single entry under the private key `token`.
+    fn private_entry(&self, token: &str) -> Option<&Content<'de>> {
+        match self.as_map()? {
+            [(k, v)] if k.as_str() == Some(token) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Copy borrowed strings and bytes so the content outlives its input.
+    pub fn into_owned(self) -> Content<'static> {
+        match self {
+            Content::Bool(b) => Content::Bool(b),
+            Content::U8(n) => Content::U8(n),
+            Content::U16(n) => Content::U16(n),
+            Content::U32(n) => Content::U32(n),
+            Content::U64(n) => Content::U64(n),
+            Content::U128(n) => Content::U128(n),
+            Content::I8(n) => Content::I8(n),
+            Content::I16(n) => Content::I16(n),
+            Content::I32(n) => Content::I32(n),
+            Content::I64(n) => Content::I64(n),
+            Content::I128(n) => Content::I128(n),
+            Content::F32(f) => Content::F32(f),
+            Content::F64(f) => Content::F64(f),
+            Content::Char(c) => Content::Char(c),
+            Content::String(s) => Content::String(s),
+            Content::Str(s) => Content::String(s.to_owned()),
+            Content::ByteBuf(b) => Content::ByteBuf(b),
+            Content::Bytes(b) => Content::ByteBuf(b.to_owned()),
+            Content::None => Content::None,
+            Content::Some(c) => Content::Some(Box::new(c.into_owned())),
+            Content::Unit => Content::Unit,
+            Content::Newtype(c) => Content::Newtype(Box::new(c.into_owned())),
+            Content::Seq(items) => {
+                Content::Seq(items.into_iter().map(Content::into_owned).collect())
+            }
+            Content::Map(entries) => Content::Map(
+                entries
+                    .into_iter()
+                    .map(|(k, v)| (k.into_owned(), v.into_owned()))
+                    .collect(),
+            ),
+        }
+    }
+
     #[cold]
-    fn unexpected(&self) -> Unexpected<'_> {
+    pub(crate) fn unexpected(&self) -> Unexpected<'_> {
         match *self {
             Content::Bool(b) => Unexpected::Bool(b),
             Content::U8(n) => Unexpected::Unsigned(n as u64),
             Content::U16(n) => Unexpected::Unsigned(n as u64),
             Content::U32(n) => Unexpected::Unsigned(n as u64),
             Content::U64(n) => Unexpected::Unsigned(n),
+            Content::U128(n) => {
+                u64::try_from(n).map_or(Unexpected::Other("u128"), Unexpected::Unsigned)
+            }
             Content::I8(n) => Unexpected::Signed(n as i64),
             Content::I16(n) => Unexpected::Signed(n as i64),
             Content::I32(n) => Unexpected::Signed(n as i64),
             Content::I64(n) => Unexpected::Signed(n),
+            Content::I128(n) => {
+                i64::try_from(n).map_or(Unexpected::Other("i128"), Unexpected::Signed)
+            }
             Content::F32(f) => Unexpected::Float(f as f64),
             Content::F64(f) => Unexpected::Float(f),
             Content::Char(c) => Unexpected::Char(c),
@@ -92,6 +282,321 @@ impl<'de> Content<'de> {
     }
 }
 
+impl PartialEq for Content<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        use Content::*;
+        match (self, other) {
+            (Bool(a), Bool(b)) => a == b,
+            (U8(a), U8(b)) => a == b,
+            (U16(a), U16(b)) => a == b,
+            (U32(a), U32(b)) => a == b,
+            (U64(a), U64(b)) => a == b,
+            (U128(a), U128(b)) => a == b,
+            (I8(a), I8(b)) => a == b,
+            (I16(a), I16(b)) => a == b,
+            (I32(a), I32(b)) => a == b,
+            (I64(a), I64(b)) => a == b,
+            (I128(a), I128(b)) => a == b,
+            (F32(a), F32(b)) => a == b,
+            (F64(a), F64(b)) => a == b,
+            (Char(a), Char(b)) => a == b,
+            (String(_) | Str(_), String(_) | Str(_)) => self.as_str() == other.as_str(),
+            (ByteBuf(_) | Bytes(_), ByteBuf(_) | Bytes(_)) => self.as_bytes() == other.as_bytes(),
+            (None, None) | (Unit, Unit) => true,
+            (Some(a), Some(b)) | (Newtype(a), Newtype(b)) => a == b,
+            (Seq(a), Seq(b)) => a == b,
+            (Map(a), Map(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+macro_rules! content_from {
+    ($($ty:ty => $variant:ident,)*) => {
+        $(
+            impl From<$ty> for Content<'_> {
+                fn from(v: $ty) -> Self {
+                    Content::$variant(v)
+                }
+            }
+        )*
+    };
+}
+
+content_from! {
+    bool => Bool,
+    u8 => U8,
+    u16 => U16,
+    u32 => U32,
+    u64 => U64,
+    u128 => U128,
+    i8 => I8,
+    i16 => I16,
+    i32 => I32,
+    i64 => I64,
+    i128 => I128,
+    f32 => F32,
+    f64 => F64,
+    char => Char,
+    String => String,
+    Vec<u8> => ByteBuf,
+}
+
+impl<'de> From<&'de str> for Content<'de> {
+    fn from(v: &'de str) -> Self {
+        Content::Str(v)
+    }
+}
+
+impl<'de> From<&'de [u8]> for Content<'de> {
+    fn from(v: &'de [u8]) -> Self {
+        Content::Bytes(v)
+    }
+}
+
+impl<'de> From<Vec<Content<'de>>> for Content<'de> {
+    fn from(v: Vec<Content<'de>>) -> Self {
+        Content::Seq(v)
+    }
+}
+
+impl<'de> From<Vec<(Content<'de>, Content<'de>)>> for Content<'de> {
+    fn from(v: Vec<(Content<'de>, Content<'de>)>) -> Self {
+        Content::Map(v)
+    }
+}
+
+impl<'de, T: Into<Content<'de>>> From<Option<T>> for Content<'de> {
+    fn from(v: Option<T>) -> Self {
+        match v {
+            Option::Some(v) => Content::Some(Box::new(v.into())),
+            Option::None => Content::None,
+        }
+    }
+}
+
+impl<'de, T: Into<Content<'de>>> FromIterator<T> for Content<'de> {
+    /// Collect into a `Seq`.
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Content::Seq(iter.into_iter().map(Into::into).collect())
+    }
+}
+
+/// Converts like serializing the content with `serde_json::to_value`, except
+/// that it cannot fail: map keys that are not strings are written as JSON
+/// text, as `serde_json` does for integer keys, non-finite floats become
+/// `null`, and 128-bit integers out of range of 64 bits become floats unless
+/// `serde_json` has `arbitrary_precision`. Arbitrary precision numbers and raw
+/// values, which `serde_json` passes as maps under a private key, become the
+/// number or value they stand for.
+#[cfg(feature = "serde_json")]
+impl From<Content<'_>> for serde_json::Value {
+    fn from(content: Content<'_>) -> Self {
+        use serde_json::Value;
+
+        if let Some(number) = content.as_number_str().and_then(|n| n.parse().ok()) {
+            return Value::Number(number);
+        }
+        let raw = content
+            .private_entry(RAW_VALUE_TOKEN)
+            .and_then(Content::as_str);
+        if let Some(value) = raw.and_then(|raw| serde_json::from_str(raw).ok()) {
+            return value;
+        }
+
+        match content {
+            Content::Bool(b) => Value::Bool(b),
+            Content::U8(n) => n.into(),
+            Content::U16(n) => n.into(),
+            Content::U32(n) => n.into(),
+            Content::U64(n) => n.into(),
+            Content::U128(n) => {
+                u64::try_from(n).map_or_else(|_| json_digits(&n.to_string()), Value::from)
+            }
+            Content::I8(n) => n.into(),
+            Content::I16(n) => n.into(),
+            Content::I32(n) => n.into(),
+            Content::I64(n) => n.into(),
+            Content::I128(n) => {
+                i64::try_from(n).map_or_else(|_| json_digits(&n.to_string()), Value::from)
+            }
+            Content::F32(f) => json_float(f as f64),
+            Content::F64(f) => json_float(f),
+            Content::Char(c) => Value::String(c.into()),
+            Content::String(s) => Value::String(s),
+            Content::Str(s) => Value::String(s.into()),
+            Content::ByteBuf(b) => b.into(),
+            Content::Bytes(b) => b.into(),
+            Content::None | Content::Unit => Value::Null,
+            Content::Some(c) | Content::Newtype(c) => (*c).into(),
+            Content::Seq(items) => Value::Array(items.into_iter().map(Value::from).collect()),
+            Content::Map(entries) => Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(k, v)| {
+                        let key = match k.as_str() {
+                            Some(key) => key.to_owned(),
+                            None => match Value::from(k) {
+                                Value::String(key) => key,
+                                key => key.to_string(),
+                            },
+                        };
+                        (key, v.into())
+                    })
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// Converts like deserializing the value into `Content`, `null` becomes
+/// `Unit`.
+#[cfg(feature = "serde_json")]
+impl From<serde_json::Value> for Content<'static> {
+    fn from(value: serde_json::Value) -> Self {
+        use serde_json::Value;
+
+        match value {
+            Value::Null => Content::Unit,
+            Value::Bool(b) => Content::Bool(b),
+            Value::Number(n) => json_number(&n),
+            Value::String(s) => Content::String(s),
+            Value::Array(items) => Content::Seq(items.into_iter().map(Content::from).collect()),
+            Value::Object(entries) => Content::Map(
+                entries
+                    .into_iter()
+                    .map(|(k, v)| (Content::String(k), v.into()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// Borrows the strings of the value instead of copying them.
+#[cfg(feature = "serde_json")]
+impl<'de> From<&'de serde_json::Value> for Content<'de> {
+    fn from(value: &'de serde_json::Value) -> Self {
+        use serde_json::Value;
+
+        match value {
+            Value::Null => Content::Unit,
+            Value::Bool(b) => Content::Bool(*b),
+            Value::Number(n) => json_number(n),
+            Value::String(s) => Content::Str(s),
+            Value::Array(items) => Content::Seq(items.iter().map(Content::from).collect()),
+            Value::Object(entries) => Content::Map(
+                entries
+                    .iter()
+                    .map(|(k, v)| (Content::Str(k), v.into()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "serde_json")]
+fn json_float(f: f64) -> serde_json::Value {
+    serde_json::Number::from_f64(f).map_or(serde_json::Value::Null, serde_json::Value::Number)
+}
+
+/// A number from its digits, exact if `serde_json` has `arbitrary_precision`
+/// and the nearest float otherwise.
+#[cfg(feature = "serde_json")]
+fn json_digits(digits: &str) -> serde_json::Value {
+    digits
+        .parse()
+        .map_or(serde_json::Value::Null, serde_json::Value::Number)
+}
+
+/// Converts like deserializing the number. Only with `arbitrary_precision`
+/// can it hold integers out of range of 64 bits, which become `U128` or
+/// `I128`, or digits an `f64` would not keep, which stay as the map
+/// `serde_json` buffers them as.
+#[cfg(feature = "serde_json")]
+fn json_number(n: &serde_json::Number) -> Content<'static> {
+    if let Some(n) = n.as_u64() {
+        return Content::U64(n);
+    } else if let Some(n) = n.as_i64() {
+        return Content::I64(n);
+    }
+
+    let exact = |f: &f64| serde_json::Number::from_f64(*f).as_ref() == Some(n);
+    if n.is_f64()
+        && let Some(f) = n.as_f64().filter(exact)
+    {
+        return Content::F64(f);
+    }
+
+    let digits = n.to_string();
+    if let Ok(n) = digits.parse() {
+        Content::U128(n)
+    } else if let Ok(n) = digits.parse() {
+        Content::I128(n)
+    } else {
+        Content::Map(vec![(Content::Str(NUMBER_TOKEN), Content::String(digits))])
+    }
+}
+
+impl Serialize for Content<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::{SerializeMap, SerializeSeq, SerializeStruct};
+
+        // `serde_json` writes the text of its private maps back out as-is
+        // when it is given them as a struct of the same name, like
+        // `serde_json::Number` and `RawValue` serialize themselves.
+        for token in [NUMBER_TOKEN, RAW_VALUE_TOKEN] {
+            if let Some(text) = self.private_entry(token) {
+                let mut s = serializer.serialize_struct(token, 1)?;
+                s.serialize_field(token, text)?;
+                return s.end();
+            }
+        }
+
+        match *self {
+            Content::Bool(b) => serializer.serialize_bool(b),
+            Content::U8(n) => serializer.serialize_u8(n),
+            Content::U16(n) => serializer.serialize_u16(n),
+            Content::U32(n) => serializer.serialize_u32(n),
+            Content::U64(n) => serializer.serialize_u64(n),
+            Content::U128(n) => serializer.serialize_u128(n),
+            Content::I8(n) => serializer.serialize_i8(n),
+            Content::I16(n) => serializer.serialize_i16(n),
+            Content::I32(n) => serializer.serialize_i32(n),
+            Content::I64(n) => serializer.serialize_i64(n),
+            Content::I128(n) => serializer.serialize_i128(n),
+            Content::F32(f) => serializer.serialize_f32(f),
+            Content::F64(f) => serializer.serialize_f64(f),
+            Content::Char(c) => serializer.serialize_char(c),
+            Content::String(ref s) => serializer.serialize_str(s),
+            Content::Str(s) => serializer.serialize_str(s),
+            Content::ByteBuf(ref b) => serializer.serialize_bytes(b),
+            Content::Bytes(b) => serializer.serialize_bytes(b),
+            Content::None => serializer.serialize_none(),
+            Content::Some(ref c) => serializer.serialize_some(&**c),
+            Content::Unit => serializer.serialize_unit(),
+            // The name of the newtype is not buffered
+            Content::Newtype(ref c) => serializer.serialize_newtype_struct("", &**c),
+            Content::Seq(ref items) => {
+                let mut seq = serializer.serialize_seq(Some(items.len()))?;
+                for item in items {
+                    seq.serialize_element(item)?;
+                }
+                seq.end()
+            }
+            Content::Map(ref entries) => {
+                let mut map = serializer.serialize_map(Some(entries.len()))?;
+                for (k, v) in entries {
+                    map.serialize_entry(k, v)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
 impl<'de> Deserialize<'de> for Content<'de> {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -100,7 +605,9 @@ impl<'de> Deserialize<'de> for Content<'de> {
         // Untagged and internally tagged enums are only supported in
         // self-describing formats.
         let visitor = ContentVisitor { value: PhantomData };
-        deserializer.deserialize_any(visitor)
+        let content = deserializer.deserialize_any(visitor)?;
+        limits::charge(&content)?;
+        Ok(content)
     }
 }
 
@@ -127,13 +634,13 @@ where
 }
 
 /// Used to capture data in [`Content`] from other deserializers.
-/// Cannot capture externally tagged enums, `i128` and `u128`.
-struct ContentVisitor<'de> {
+/// Cannot capture externally tagged enums.
+pub(crate) struct ContentVisitor<'de> {
     value: PhantomData<Content<'de>>,
 }
 
 impl<'de> ContentVisitor<'de> {
-    fn new() -> Self {
+    pub(crate) fn new() -> Self {
         ContentVisitor { value: PhantomData }
     }
 }
@@ -231,6 +738,13 @@ impl<'de> Visitor<'de> for ContentVisitor<'de> {
         Ok(Content::I64(value))
     }
 
+    fn visit_i128<F>(self, value: i128) -> Result<Self::Value, F>
+    where
+        F: de::Error,
+    {
+        Ok(Content::I128(value))
+    }
+
     fn visit_u8<F>(self, value: u8) -> Result<Self::Value, F>
     where
         F: de::Error,
@@ -259,6 +773,13 @@ impl<'de> Visitor<'de> for ContentVisitor<'de> {
         Ok(Content::U64(value))
     }
 
+    fn visit_u128<F>(self, value: u128) -> Result<Self::Value, F>
+    where
+        F: de::Error,
+    {
+        Ok(Content::U128(value))
+    }
+
     fn visit_f32<F>(self, value: f32) -> Result<Self::Value, F>
     where
         F: de::Error,
@@ -284,6 +805,7 @@ impl<'de> Visitor<'de> for ContentVisitor<'de> {
     where
         F: de::Error,
     {
+        tri!(limits::check_copy(value.len()));
         Ok(Content::String(value.into()))
     }
 
@@ -298,6 +820,7 @@ impl<'de> Visitor<'de> for ContentVisitor<'de> {
     where
         F: de::Error,
     {
+        tri!(limits::check_copy(value.len()));
         Ok(Content::String(value))
     }
 
@@ -305,6 +828,7 @@ impl<'de> Visitor<'de> for ContentVisitor<'de> {
     where
         F: de::Error,
     {
+        tri!(limits::check_copy(value.len()));
         Ok(Content::ByteBuf(value.into()))
     }
 
@@ -319,6 +843,7 @@ impl<'de> Visitor<'de> for ContentVisitor<'de> {
     where
         F: de::Error,
     {
+        tri!(limits::check_copy(value.len()));
         Ok(Content::ByteBuf(value))
     }
 
@@ -340,6 +865,7 @@ impl<'de> Visitor<'de> for ContentVisitor<'de> {
     where
         D: Deserializer<'de>,
     {
+        let _depth = tri!(limits::enter_nested());
         let v = tri!(Deserialize::deserialize(deserializer));
         Ok(Content::Some(Box::new(v)))
     }
@@ -348,6 +874,7 @@ impl<'de> Visitor<'de> for ContentVisitor<'de> {
     where
         D: Deserializer<'de>,
     {
+        let _depth = tri!(limits::enter_nested());
         let v = tri!(Deserialize::deserialize(deserializer));
         Ok(Content::Newtype(Box::new(v)))
     }
@@ -356,9 +883,11 @@ impl<'de> Visitor<'de> for ContentVisitor<'de> {
     where
         V: SeqAccess<'de>,
     {
-        let mut vec = Vec::<Content>::with_capacity(visitor.size_hint().unwrap_or(0));
+        let _depth = tri!(limits::enter_nested());
+        let mut vec = Vec::<Content>::with_capacity(limits::capacity(visitor.size_hint()));
         while let Some(e) = tri!(visitor.next_element()) {
             vec.push(e);
+            tri!(limits::check_len(vec.len()));
         }
         Ok(Content::Seq(vec))
     }
@@ -367,9 +896,12 @@ impl<'de> Visitor<'de> for ContentVisitor<'de> {
     where
         V: MapAccess<'de>,
     {
-        let mut vec = Vec::<(Content, Content)>::with_capacity(visitor.size_hint().unwrap_or(0));
+        let _depth = tri!(limits::enter_nested());
+        let mut vec =
+            Vec::<(Content, Content)>::with_capacity(limits::capacity(visitor.size_hint()));
         while let Some(kv) = tri!(visitor.next_entry()) {
             vec.push(kv);
+            tri!(limits::check_len(vec.len()));
         }
         Ok(Content::Map(vec))
     }
@@ -393,7 +925,7 @@ pub enum TagOrContent<'de> {
 }
 
 /// Serves as a seed for deserializing a key of internally tagged enum.
-/// Cannot capture externally tagged enums, `i128` and `u128`.
+/// Cannot capture externally tagged enums.
 struct TagOrContentVisitor<'de> {
     name: &'static str,
     value: PhantomData<TagOrContent<'de>>,
@@ -473,6 +1005,15 @@ impl<'de> Visitor<'de> for TagOrContentVisitor<'de> {
             .map(TagOrContent::Content)
     }
 
+    fn visit_i128<F>(self, value: i128) -> Result<Self::Value, F>
+    where
+        F: de::Error,
+    {
+        ContentVisitor::new()
+            .visit_i128(value)
+            .map(TagOrContent::Content)
+    }
+
     fn visit_u8<F>(self, value: u8) -> Result<Self::Value, F>
     where
         F: de::Error,
@@ -509,6 +1050,15 @@ impl<'de> Visitor<'de> for TagOrContentVisitor<'de> {
             .map(TagOrContent::Content)
     }
 
+    fn visit_u128<F>(self, value: u128) -> Result<Self::Value, F>
+    where
+        F: de::Error,
+    {
+        ContentVisitor::new()
+            .visit_u128(value)
+            .map(TagOrContent::Content)
+    }
+
     fn visit_f32<F>(self, value: f32) -> Result<Self::Value, F>
     where
         F: de::Error,
@@ -924,11 +1474,13 @@ where
             Content::U16(v) => visitor.visit_u16(v),
             Content::U32(v) => visitor.visit_u32(v),
             Content::U64(v) => visitor.visit_u64(v),
+            Content::U128(v) => visitor.visit_u128(v),
             Content::I8(v) => visitor.visit_i8(v),
             Content::I16(v) => visitor.visit_i16(v),
             Content::I32(v) => visitor.visit_i32(v),
             Content::I64(v) => visitor.visit_i64(v),
-            _ => Err(self.invalid_type(&visitor)),
+            Content::I128(v) => visitor.visit_i128(v),
+            _ => self.deserialize_number_str(visitor),
         }
     }
 
@@ -943,11 +1495,24 @@ where
             Content::U16(v) => visitor.visit_u16(v),
             Content::U32(v) => visitor.visit_u32(v),
             Content::U64(v) => visitor.visit_u64(v),
+            Content::U128(v) => visitor.visit_u128(v),
             Content::I8(v) => visitor.visit_i8(v),
             Content::I16(v) => visitor.visit_i16(v),
             Content::I32(v) => visitor.visit_i32(v),
             Content::I64(v) => visitor.visit_i64(v),
-            _ => Err(self.invalid_type(&visitor)),
+            Content::I128(v) => visitor.visit_i128(v),
+            _ => self.deserialize_number_str(visitor),
+        }
+    }
+
+    /// A number `serde_json` buffered with `arbitrary_precision`.
+    fn deserialize_number_str<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        match self.content.as_number_str() {
+            Some(digits) => visit_number_str(digits, visitor),
+            None => Err(self.invalid_type(&visitor)),
         }
     }
 }
@@ -963,6 +1528,53 @@ where
     Ok(value)
 }
 
+/// Visit the digits of an arbitrary precision number as the first of `u64`,
+/// `i64`, `u128`, `i128` and `f64` that holds them, like `serde_json` parses
+/// numbers.
+pub(crate) fn visit_number_str<'de, V, E>(digits: &str, visitor: V) -> Result<V::Value, E>
+where
+    V: Visitor<'de>,
+    E: de::Error,
+{
+    if let Ok(n) = digits.parse() {
+        visitor.visit_u64(n)
+    } else if let Ok(n) = digits.parse() {
+        visitor.visit_i64(n)
+    } else if let Ok(n) = digits.parse() {
+        visitor.visit_u128(n)
+    } else if let Ok(n) = digits.parse() {
+        visitor.visit_i128(n)
+    } else if let Ok(f) = digits.parse() {
+        visitor.visit_f64(f)
+    } else {
+        Err(de::Error::invalid_value(Unexpected::Str(digits), &visitor))
+    }
+}
+
+/// Replay content into `serde_json`'s `RawValue`, which asks for the JSON
+/// text of the value as a map with a single entry under a private key. The
+/// text is serialized again from the content, so it holds the same value but
+/// not necessarily the same spelling as the input.
+pub(crate) fn visit_raw_value<'de, V, E>(content: &Content<'de>, visitor: V) -> Result<V::Value, E>
+where
+    V: Visitor<'de>,
+    E: de::Error,
+{
+    if content.private_entry(RAW_VALUE_TOKEN).is_some() {
+        return visit_content_map_ref(content.as_map().unwrap_or_default(), visitor);
+    }
+    #[cfg(feature = "serde_json")]
+    {
+        let json = tri!(serde_json::to_string(content).map_err(de::Error::custom));
+        let entry = [(Content::Str(RAW_VALUE_TOKEN), Content::String(json))];
+        visit_content_map_ref(&entry, visitor)
+    }
+    #[cfg(not(feature = "serde_json"))]
+    Err(de::Error::custom(
+        "buffered input can only be read as a `RawValue` with the `serde_json` feature",
+    ))
+}
+
 fn visit_content_map<'de, V, E>(
     content: Vec<(Content<'de>, Content<'de>)>,
     visitor: V,
@@ -996,10 +1608,12 @@ where
             Content::U16(v) => visitor.visit_u16(v),
             Content::U32(v) => visitor.visit_u32(v),
             Content::U64(v) => visitor.visit_u64(v),
+            Content::U128(v) => visitor.visit_u128(v),
             Content::I8(v) => visitor.visit_i8(v),
             Content::I16(v) => visitor.visit_i16(v),
             Content::I32(v) => visitor.visit_i32(v),
             Content::I64(v) => visitor.visit_i64(v),
+            Content::I128(v) => visitor.visit_i128(v),
             Content::F32(v) => visitor.visit_f32(v),
             Content::F64(v) => visitor.visit_f64(v),
             Content::Char(v) => visitor.visit_char(v),
@@ -1054,6 +1668,13 @@ where
         self.deserialize_integer(visitor)
     }
 
+    fn deserialize_i128<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_integer(visitor)
+    }
+
     fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
@@ -1082,6 +1703,13 @@ where
         self.deserialize_integer(visitor)
     }
 
+    fn deserialize_u128<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_integer(visitor)
+    }
+
     fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
@@ -1211,12 +1839,13 @@ where
         }
     }
 
-    fn deserialize_newtype_struct<V>(self, _name: &str, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_newtype_struct<V>(self, name: &str, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
         match self.content {
             Content::Newtype(v) => visitor.visit_newtype_struct(ContentDeserializer::new(*v)),
+            _ if name == RAW_VALUE_TOKEN => visit_raw_value(&self.content, visitor),
             _ => visitor.visit_newtype_struct(self),
         }
     }
@@ -1328,6 +1957,8 @@ where
             Content::ByteBuf(v) => visitor.visit_byte_buf(v),
             Content::Bytes(v) => visitor.visit_borrowed_bytes(v),
             Content::U8(v) => visitor.visit_u8(v),
+            Content::U16(v) => visitor.visit_u16(v),
+            Content::U32(v) => visitor.visit_u32(v),
             Content::U64(v) => visitor.visit_u64(v),
             _ => Err(self.invalid_type(&visitor)),
         }
@@ -1390,10 +2021,12 @@ where
     map_key_integer_method!(owned deserialize_i16, visit_i16, i16);
     map_key_integer_method!(owned deserialize_i32, visit_i32, i32);
     map_key_integer_method!(owned deserialize_i64, visit_i64, i64);
+    map_key_integer_method!(owned deserialize_i128, visit_i128, i128);
     map_key_integer_method!(owned deserialize_u8, visit_u8, u8);
     map_key_integer_method!(owned deserialize_u16, visit_u16, u16);
     map_key_integer_method!(owned deserialize_u32, visit_u32, u32);
     map_key_integer_method!(owned deserialize_u64, visit_u64, u64);
+    map_key_integer_method!(owned deserialize_u128, visit_u128, u128);
 
     fn deserialize_newtype_struct<V>(self, _name: &str, visitor: V) -> Result<V::Value, Self::Error>
     where
@@ -1561,11 +2194,13 @@ where
             Content::U16(v) => visitor.visit_u16(v),
             Content::U32(v) => visitor.visit_u32(v),
             Content::U64(v) => visitor.visit_u64(v),
+            Content::U128(v) => visitor.visit_u128(v),
             Content::I8(v) => visitor.visit_i8(v),
             Content::I16(v) => visitor.visit_i16(v),
             Content::I32(v) => visitor.visit_i32(v),
             Content::I64(v) => visitor.visit_i64(v),
-            _ => Err(self.invalid_type(&visitor)),
+            Content::I128(v) => visitor.visit_i128(v),
+            _ => self.deserialize_number_str(visitor),
         }
     }
 
@@ -1580,11 +2215,24 @@ where
             Content::U16(v) => visitor.visit_u16(v),
             Content::U32(v) => visitor.visit_u32(v),
             Content::U64(v) => visitor.visit_u64(v),
+            Content::U128(v) => visitor.visit_u128(v),
             Content::I8(v) => visitor.visit_i8(v),
             Content::I16(v) => visitor.visit_i16(v),
             Content::I32(v) => visitor.visit_i32(v),
             Content::I64(v) => visitor.visit_i64(v),
-            _ => Err(self.invalid_type(&visitor)),
+            Content::I128(v) => visitor.visit_i128(v),
+            _ => self.deserialize_number_str(visitor),
+        }
+    }
+
+    /// A number `serde_json` buffered with `arbitrary_precision`.
+    fn deserialize_number_str<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        match self.content.as_number_str() {
+            Some(digits) => visit_number_str(digits, visitor),
+            None => Err(self.invalid_type(&visitor)),
         }
     }
 }
@@ -1611,7 +2259,7 @@ where
     V: Visitor<'de>,
     E: de::Error,
 {
-    let map = content.iter().map(|(k, v)| (MapKeyContentRef(k), &*v));
+    let map = content.iter().map(|(k, v)| (MapKeyContentRef(k), v));
     let mut map_visitor = MapDeserializer::new(map);
     let value = tri!(visitor.visit_map(&mut map_visitor));
     tri!(map_visitor.end());
@@ -1636,10 +2284,12 @@ where
             Content::U16(v) => visitor.visit_u16(v),
             Content::U32(v) => visitor.visit_u32(v),
             Content::U64(v) => visitor.visit_u64(v),
+            Content::U128(v) => visitor.visit_u128(v),
             Content::I8(v) => visitor.visit_i8(v),
             Content::I16(v) => visitor.visit_i16(v),
             Content::I32(v) => visitor.visit_i32(v),
             Content::I64(v) => visitor.visit_i64(v),
+            Content::I128(v) => visitor.visit_i128(v),
             Content::F32(v) => visitor.visit_f32(v),
             Content::F64(v) => visitor.visit_f64(v),
             Content::Char(v) => visitor.visit_char(v),
@@ -1694,6 +2344,13 @@ where
         self.deserialize_integer(visitor)
     }
 
+    fn deserialize_i128<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_integer(visitor)
+    }
+
     fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
@@ -1722,6 +2379,13 @@ where
         self.deserialize_integer(visitor)
     }
 
+    fn deserialize_u128<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_integer(visitor)
+    }
+
     fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
@@ -1831,7 +2495,7 @@ where
         self.deserialize_unit(visitor)
     }
 
-    fn deserialize_newtype_struct<V>(self, _name: &str, visitor: V) -> Result<V::Value, E>
+    fn deserialize_newtype_struct<V>(self, name: &str, visitor: V) -> Result<V::Value, E>
     where
         V: Visitor<'de>,
     {
@@ -1839,6 +2503,7 @@ where
         //      newtype_struct
         match *self.content {
             Content::Newtype(ref v) => visitor.visit_newtype_struct(ContentRefDeserializer::new(v)),
+            _ if name == RAW_VALUE_TOKEN => visit_raw_value(self.content, visitor),
             // This case is to support data formats that encode newtype
             // structs and their underlying data the same, with no
             // indication whether a newtype wrapper was present. For example
@@ -1963,6 +2628,8 @@ where
             Content::ByteBuf(ref v) => visitor.visit_bytes(v),
             Content::Bytes(v) => visitor.visit_borrowed_bytes(v),
             Content::U8(v) => visitor.visit_u8(v),
+            Content::U16(v) => visitor.visit_u16(v),
+            Content::U32(v) => visitor.visit_u32(v),
             Content::U64(v) => visitor.visit_u64(v),
             _ => Err(self.invalid_type(&visitor)),
         }
@@ -2040,10 +2707,12 @@ where
     map_key_integer_method!(ref deserialize_i16, visit_i16, i16);
     map_key_integer_method!(ref deserialize_i32, visit_i32, i32);
     map_key_integer_method!(ref deserialize_i64, visit_i64, i64);
+    map_key_integer_method!(ref deserialize_i128, visit_i128, i128);
     map_key_integer_method!(ref deserialize_u8, visit_u8, u8);
     map_key_integer_method!(ref deserialize_u16, visit_u16, u16);
     map_key_integer_method!(ref deserialize_u32, visit_u32, u32);
     map_key_integer_method!(ref deserialize_u64, visit_u64, u64);
+    map_key_integer_method!(ref deserialize_u128, visit_u128, u128);
 
     fn deserialize_newtype_struct<V>(self, _name: &str, visitor: V) -> Result<V::Value, Self::Error>
     where
@@ -2298,3 +2967,252 @@ impl<'de, 'a> Visitor<'de> for UntaggedUnitVisitor<'a> {
         Ok(())
     }
 }
+
+/// Deserializes a `#[serde_implicit(flatten)]` field from the entries of a
+/// struct variant that no other field claimed. Structs take out the entries
+/// for their own fields, maps see every entry still left.
+pub struct FlatMapDeserializer<'a, 'de: 'a, E>(
+    pub &'a mut Vec<Option<(Content<'de>, Content<'de>)>>,
+    pub PhantomData<E>,
+);
+
+impl<'a, 'de, E> FlatMapDeserializer<'a, 'de, E>
+where
+    E: de::Error,
+{
+    pub fn new(entries: &'a mut Vec<Option<(Content<'de>, Content<'de>)>>) -> Self {
+        FlatMapDeserializer(entries, PhantomData)
+    }
+
+    fn deserialize_other<V>() -> Result<V, E> {
+        Err(de::Error::custom("can only flatten structs and maps"))
+    }
+}
+
+macro_rules! forward_to_deserialize_other {
+    ($($func:ident ($($arg:ty),*))*) => {
+        $(
+            fn $func<V>(self, $(_: $arg,)* _visitor: V) -> Result<V::Value, Self::Error>
+            where
+                V: Visitor<'de>,
+            {
+                Self::deserialize_other()
+            }
+        )*
+    }
+}
+
+impl<'a, 'de, E> Deserializer<'de> for FlatMapDeserializer<'a, 'de, E>
+where
+    E: de::Error,
+{
+    type Error = E;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        for entry in self.0 {
+            if let Some((key, value)) = flat_map_take_entry(entry, variants) {
+                return visitor.visit_enum(EnumDeserializer::new(key, Some(value)));
+            }
+        }
+
+        Err(de::Error::custom(format_args!(
+            "no variant of enum {} found in flattened data",
+            name
+        )))
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_map(FlatMapAccess {
+            iter: self.0.iter(),
+            pending_content: None,
+            _marker: PhantomData,
+        })
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        _: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_map(FlatStructAccess {
+            iter: self.0.iter_mut(),
+            pending_content: None,
+            fields,
+            _marker: PhantomData,
+        })
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &str, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V>(
+        self,
+        _: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_other! {
+        deserialize_bool()
+        deserialize_i8()
+        deserialize_i16()
+        deserialize_i32()
+        deserialize_i64()
+        deserialize_u8()
+        deserialize_u16()
+        deserialize_u32()
+        deserialize_u64()
+        deserialize_f32()
+        deserialize_f64()
+        deserialize_char()
+        deserialize_str()
+        deserialize_string()
+        deserialize_bytes()
+        deserialize_byte_buf()
+        deserialize_seq()
+        deserialize_tuple(usize)
+        deserialize_tuple_struct(&'static str, usize)
+        deserialize_identifier()
+    }
+}
+
+struct FlatMapAccess<'a, 'de: 'a, E> {
+    iter: std::slice::Iter<'a, Option<(Content<'de>, Content<'de>)>>,
+    pending_content: Option<&'a Content<'de>>,
+    _marker: PhantomData<E>,
+}
+
+impl<'a, 'de, E> MapAccess<'de> for FlatMapAccess<'a, 'de, E>
+where
+    E: de::Error,
+{
+    type Error = E;
+
+    fn next_key_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        for item in &mut self.iter {
+            // Items in the vector are nulled out when used by a struct.
+            if let Some((ref key, ref content)) = *item {
+                // Do not take(), instead borrow this entry, leaving it
+                // available for later flattened fields.
+                self.pending_content = Some(content);
+                return seed.deserialize(ContentRefDeserializer::new(key)).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    fn next_value_seed<T>(&mut self, seed: T) -> Result<T::Value, Self::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self.pending_content.take() {
+            Some(value) => seed.deserialize(ContentRefDeserializer::new(value)),
+            None => Err(de::Error::custom("value is missing")),
+        }
+    }
+}
+
+struct FlatStructAccess<'a, 'de: 'a, E> {
+    iter: std::slice::IterMut<'a, Option<(Content<'de>, Content<'de>)>>,
+    pending_content: Option<Content<'de>>,
+    fields: &'static [&'static str],
+    _marker: PhantomData<E>,
+}
+
+impl<'a, 'de, E> MapAccess<'de> for FlatStructAccess<'a, 'de, E>
+where
+    E: de::Error,
+{
+    type Error = E;
+
+    fn next_key_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        for entry in self.iter.by_ref() {
+            if let Some((key, content)) = flat_map_take_entry(entry, self.fields) {
+                self.pending_content = Some(content);
+                return seed.deserialize(ContentDeserializer::new(key)).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    fn next_value_seed<T>(&mut self, seed: T) -> Result<T::Value, Self::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self.pending_content.take() {
+            Some(value) => seed.deserialize(ContentDeserializer::new(value)),
+            None => Err(de::Error::custom("value is missing")),
+        }
+    }
+}
+
+/// Claims one of the entries if its key is one of the recognized names.
+fn flat_map_take_entry<'de>(
+    entry: &mut Option<(Content<'de>, Content<'de>)>,
+    recognized: &[&str],
+) -> Option<(Content<'de>, Content<'de>)> {
+    // Entries in the vector are nulled out when used by a struct.
+    let is_recognized = match entry {
+        None => false,
+        Some((k, _v)) => k.as_str().is_some_and(|name| recognized.contains(&name)),
+    };
+
+    if is_recognized { entry.take() } else { None }
+}
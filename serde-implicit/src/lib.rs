@@ -5,3 +5,31 @@ pub use serde_implicit_proc::Deserialize;
 pub mod __private;
 
 pub mod content;
+pub mod limits;
+#[cfg(feature = "tape")]
+pub mod tape;
+
+pub use limits::Limits;
+
+use content::Content;
+
+/// Deserialize from [`Content`] that is already buffered, by reference.
+///
+/// Implemented by `#[derive(serde_implicit::Deserialize)]`. Variants are read
+/// straight from the borrowed content, where going through
+/// [`serde::Deserialize`] would buffer the input again.
+pub trait FromContentRef<'de>: Sized {
+    fn from_content_ref<E: serde::de::Error>(content: &Content<'de>) -> Result<Self, E>;
+}
+
+/// Deserialize `T` from a borrowed [`serde_json::Value`] without cloning it.
+///
+/// The value is still converted to a [`Content`] tree, with a `Vec` for every
+/// sequence and map, but its strings are borrowed rather than copied.
+#[cfg(feature = "serde_json")]
+pub fn from_value_ref<'de, T>(value: &'de serde_json::Value) -> Result<T, serde_json::Error>
+where
+    T: FromContentRef<'de>,
+{
+    T::from_content_ref(&Content::from(value))
+}
@@ -0,0 +1,178 @@
+//! Bounds on the input buffered while looking for an implicit tag.
+//!
+//! An implicit tag can appear anywhere in the input, so derived types buffer
+//! it into [`Content`](crate::content::Content), or a tape with the `tape`
+//! feature, first. [`Limits`] bound that buffering, for types with
+//! `#[serde_implicit(max_depth = .., max_len = .., max_bytes = ..)]` and
+//! otherwise with the limits set for the thread.
+
+use std::cell::Cell;
+
+use serde::de;
+
+use crate::content::Content;
+
+/// Bounds on buffered input, `None` meaning unbounded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// Deepest nesting of sequences, maps, options and newtypes, the input
+    /// itself being at depth 1.
+    pub max_depth: Option<usize>,
+    /// Most elements in a single sequence or entries in a single map.
+    pub max_len: Option<usize>,
+    /// Most bytes buffered for one input, counting the size of each value
+    /// plus the strings and bytes copied out of the input.
+    pub max_bytes: Option<usize>,
+}
+
+impl Limits {
+    pub const NONE: Limits = Limits {
+        max_depth: None,
+        max_len: None,
+        max_bytes: None,
+    };
+
+    /// The limits of types without their own, on this thread.
+    pub fn thread_default() -> Limits {
+        DEFAULT.with(Cell::get)
+    }
+
+    /// Set the limits of types without their own, on this thread.
+    pub fn set_thread_default(limits: Limits) {
+        DEFAULT.with(|default| default.set(limits));
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Budget {
+    limits: Limits,
+    depth: usize,
+    bytes: usize,
+}
+
+thread_local! {
+    static DEFAULT: Cell<Limits> = const { Cell::new(Limits::NONE) };
+    static BUDGET: Cell<Option<Budget>> = const { Cell::new(None) };
+}
+
+fn with_budget<R>(f: impl FnOnce(&mut Budget) -> R) -> Option<R> {
+    BUDGET.with(|cell| {
+        let mut budget = cell.get()?;
+        let res = f(&mut budget);
+        cell.set(Some(budget));
+        Some(res)
+    })
+}
+
+/// Enforces `limits` on the content buffered until it is dropped, when the
+/// enclosing budget, if any, is restored.
+#[doc(hidden)]
+pub struct BufferGuard {
+    prev: Option<Budget>,
+}
+
+impl BufferGuard {
+    pub fn enter(limits: Limits) -> Self {
+        let budget = Budget {
+            limits,
+            depth: 0,
+            bytes: 0,
+        };
+        BufferGuard {
+            prev: BUDGET.with(|cell| cell.replace(Some(budget))),
+        }
+    }
+}
+
+impl Drop for BufferGuard {
+    fn drop(&mut self) {
+        BUDGET.with(|cell| cell.set(self.prev));
+    }
+}
+
+/// One level of nesting, left when dropped.
+pub(crate) struct DepthGuard(());
+
+impl Drop for DepthGuard {
+    fn drop(&mut self) {
+        with_budget(|budget| budget.depth -= 1);
+    }
+}
+
+/// Go one level deeper into the input.
+pub(crate) fn enter_nested<E: de::Error>() -> Result<DepthGuard, E> {
+    let exceeded = with_budget(|budget| {
+        budget.depth += 1;
+        budget.limits.max_depth.filter(|&max| budget.depth > max)
+    });
+    // Built before checking so that the depth is restored on error too
+    let guard = DepthGuard(());
+    match exceeded.flatten() {
+        Some(max) => Err(E::custom(format_args!(
+            "input nested deeper than the limit of {max}"
+        ))),
+        None => Ok(guard),
+    }
+}
+
+/// Check the number of elements buffered for one sequence or map.
+pub(crate) fn check_len<E: de::Error>(len: usize) -> Result<(), E> {
+    let max_len = with_budget(|budget| budget.limits.max_len).flatten();
+    match max_len {
+        Some(max) if len > max => Err(E::custom(format_args!(
+            "sequence or map longer than the limit of {max} elements"
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Capacity to reserve for a sequence or map, not trusting the size hint
+/// past the length limit.
+pub(crate) fn capacity(hint: Option<usize>) -> usize {
+    let max_len = with_budget(|budget| budget.limits.max_len).flatten();
+    let hint = hint.unwrap_or(0);
+    max_len.map_or(hint, |max| hint.min(max))
+}
+
+/// Count a buffered value against the byte limit.
+pub(crate) fn charge<E: de::Error>(content: &Content) -> Result<(), E> {
+    let owned = match content {
+        Content::String(s) => s.len(),
+        Content::ByteBuf(b) => b.len(),
+        _ => 0,
+    };
+    charge_bytes(size_of::<Content>() + owned)
+}
+
+/// Count `bytes` buffered against the byte limit.
+pub(crate) fn charge_bytes<E: de::Error>(bytes: usize) -> Result<(), E> {
+    let exceeded = with_budget(|budget| {
+        budget.bytes += bytes;
+        budget.limits.max_bytes.filter(|&max| budget.bytes > max)
+    });
+    match exceeded.flatten() {
+        Some(max) => Err(too_large(max)),
+        None => Ok(()),
+    }
+}
+
+/// Check that a string or bytes of `len` bytes fit in the byte limit before
+/// copying them, so that a single huge one is rejected without being
+/// buffered first. They are counted once buffered.
+pub(crate) fn check_copy<E: de::Error>(len: usize) -> Result<(), E> {
+    let exceeded = with_budget(|budget| {
+        let bytes = budget.bytes.saturating_add(size_of::<Content>() + len);
+        budget.limits.max_bytes.filter(|&max| bytes > max)
+    });
+    match exceeded.flatten() {
+        Some(max) => Err(too_large(max)),
+        None => Ok(()),
+    }
+}
+
+#[cold]
+fn too_large<E: de::Error>(max: usize) -> E {
+    E::custom(format_args!(
+        "input larger than the limit of {max} buffered bytes"
+    ))
+}
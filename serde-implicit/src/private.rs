@@ -1,15 +1,316 @@
+use std::borrow::Cow;
 use std::fmt;
 use std::marker::PhantomData;
 
-use serde::de::{self, IntoDeserializer, MapAccess, Unexpected};
+use serde::de::{self, MapAccess, SeqAccess, Unexpected};
 use serde::forward_to_deserialize_any;
 use serde::{Deserialize, de::Visitor};
 
-pub use crate::content::{Content, ContentDeserializer, ContentRefDeserializer};
+use crate::content::ContentVisitor;
+pub use crate::content::{
+    Content, ContentDeserializer, ContentRefDeserializer, FlatMapDeserializer,
+};
+use crate::limits;
+pub use crate::limits::BufferGuard;
+#[cfg(feature = "tape")]
+pub use crate::tape::{Tape, TapeDeserializer, TapeRefDeserializer};
+
+/// What struct enums buffer their input into while looking for the tag: a
+/// [`Tape`] with the `tape` feature, [`Content`] otherwise.
+#[cfg(not(feature = "tape"))]
+pub type Buffered<'de> = Content<'de>;
+#[cfg(feature = "tape")]
+pub type Buffered<'de> = Tape<'de>;
+
+/// Buffer content read by other means than the tag search.
+#[cfg(not(feature = "tape"))]
+fn buffer(content: Content) -> Buffered {
+    content
+}
+#[cfg(feature = "tape")]
+fn buffer(content: Content) -> Buffered {
+    Tape::from(content)
+}
+
+/// Replay buffered input into a variant.
+#[cfg(not(feature = "tape"))]
+pub fn buffered_deserializer<'de, E>(content: Buffered<'de>) -> ContentDeserializer<'de, E> {
+    ContentDeserializer::new(content)
+}
+#[cfg(feature = "tape")]
+pub fn buffered_deserializer<'de, E>(tape: Buffered<'de>) -> TapeDeserializer<'de, E> {
+    TapeDeserializer::new(tape)
+}
+
+/// Replay buffered input into one of several variants to try.
+#[cfg(not(feature = "tape"))]
+pub fn buffered_ref_deserializer<'a, 'de, E>(
+    content: &'a Buffered<'de>,
+) -> ContentRefDeserializer<'a, 'de, E> {
+    ContentRefDeserializer::new(content)
+}
+#[cfg(feature = "tape")]
+pub fn buffered_ref_deserializer<'a, 'de, E>(
+    tape: &'a Buffered<'de>,
+) -> TapeRefDeserializer<'a, 'de, E> {
+    tape.deserializer()
+}
+
+/// [`fallthrough_keys`] of buffered input.
+#[cfg(not(feature = "tape"))]
+pub fn buffered_keys<'a>(content: &'a Buffered<'_>) -> impl Iterator<Item = &'a str> {
+    fallthrough_keys(content)
+}
+#[cfg(feature = "tape")]
+pub fn buffered_keys<'a>(tape: &'a Buffered<'_>) -> impl Iterator<Item = &'a str> {
+    tape.keys()
+}
+
+/// Per-variant tag options, implemented by the variant identifier generated
+/// for a struct enum.
+pub trait VariantTag {
+    /// Whether an explicit `null` under this variant's tag key still selects
+    /// the variant.
+    fn allow_null(&self) -> bool;
+
+    /// Which tag wins when several are found in the same input, the highest
+    /// priority commits and a tie is an error.
+    fn priority(&self) -> i32;
+}
+
+/// Name a tag key in error messages.
+fn key_name(key: &Content) -> String {
+    match *key {
+        Content::U8(n) => n.to_string(),
+        Content::U16(n) => n.to_string(),
+        Content::U32(n) => n.to_string(),
+        Content::U64(n) => n.to_string(),
+        ref k => k.as_str().unwrap_or("unknown").to_owned(),
+    }
+}
+
+/// The tag with the highest priority found so far, and the keys of two tags
+/// tied at that priority, if any.
+struct TagCandidate<T> {
+    best: Option<(T, String)>,
+    tie: Option<(String, String)>,
+}
+
+impl<T: VariantTag> TagCandidate<T> {
+    fn new() -> Self {
+        TagCandidate {
+            best: None,
+            tie: None,
+        }
+    }
+
+    /// Keep whichever of the current and the newly found tag has the highest
+    /// priority. A tie is only recorded, since a tag found later may still
+    /// outrank it.
+    fn offer(&mut self, tag: T, key_name: impl FnOnce() -> String) {
+        match &self.best {
+            Some((prev, prev_key)) => match tag.priority().cmp(&prev.priority()) {
+                std::cmp::Ordering::Less => {}
+                std::cmp::Ordering::Greater => {
+                    self.best = Some((tag, key_name()));
+                    self.tie = None;
+                }
+                std::cmp::Ordering::Equal => {
+                    if self.tie.is_none() {
+                        self.tie = Some((prev_key.clone(), key_name()));
+                    }
+                }
+            },
+            None => self.best = Some((tag, key_name())),
+        }
+    }
+
+    /// The winning tag, an error if it is tied.
+    fn finish<E: de::Error>(self) -> Result<Option<T>, E> {
+        match self.tie {
+            Some((first, second)) => Err(de::Error::custom(format_args!(
+                "found multiple implicit tag fields: `{first}` and `{second}`",
+            ))),
+            None => Ok(self.best.map(|(tag, _)| tag)),
+        }
+    }
+}
+
+/// The implicit tag found among the entries of a map, so far.
+struct TagSearch<T> {
+    tag: TagCandidate<T>,
+    null_tag: TagCandidate<T>,
+}
+
+impl<T: VariantTag> TagSearch<T> {
+    fn new() -> Self {
+        TagSearch {
+            tag: TagCandidate::new(),
+            null_tag: TagCandidate::new(),
+        }
+    }
+
+    /// Check whether the entry `k: v` is a tag.
+    fn entry<'de, E>(&mut self, k: &Content<'de>, v: &Content<'de>)
+    where
+        T: Deserialize<'de>,
+        E: de::Error,
+    {
+        if !is_key(k) {
+            return;
+        }
+
+        self.key(
+            ContentRefDeserializer::<E>::new(k),
+            matches!(v, Content::None | Content::Unit),
+            || key_name(k),
+        );
+    }
+
+    /// Check whether the entry at `k` and `v` of a tape is a tag.
+    #[cfg(feature = "tape")]
+    fn tape_entry<'de, E>(&mut self, tape: &Tape<'de>, k: usize, v: usize)
+    where
+        T: Deserialize<'de>,
+        E: de::Error,
+    {
+        if !tape.is_key(k) {
+            return;
+        }
+
+        self.key(
+            TapeRefDeserializer::<E>::new(tape, k),
+            tape.is_null(v),
+            || tape.key_name(k),
+        );
+    }
+
+    /// Check whether `key`, whose value is null or not, is a tag.
+    fn key<'de, D>(&mut self, key: D, is_null: bool, key_name: impl FnOnce() -> String)
+    where
+        T: Deserialize<'de>,
+        D: de::Deserializer<'de>,
+    {
+        // failed to parse a key, must be a non-tag field
+        let Ok(t) = T::deserialize(key) else {
+            return;
+        };
+
+        // Skip null values — they can't be a real tag, unless the variant
+        // opted in with `allow_null`, and then only if no other tag is found
+        if !is_null {
+            self.tag.offer(t, key_name);
+        } else if t.allow_null() {
+            self.null_tag.offer(t, key_name);
+        }
+    }
+
+    /// The tag found, if any.
+    fn found<E: de::Error>(self) -> Result<Option<T>, E> {
+        match self.tag.best {
+            Some(_) => self.tag.finish(),
+            None => self.null_tag.finish(),
+        }
+    }
+
+    /// The tag found, or else the fallthrough variant.
+    fn tag<E: de::Error>(self, fallthrough: Option<T>) -> Result<T, E> {
+        match (self.found()?, fallthrough) {
+            (None, None) => Err(de::Error::missing_field("tag was not found")),
+            (None, Some(default)) => Ok(default),
+            (Some(tag), _) => Ok(tag),
+        }
+    }
+
+    #[cfg(not(feature = "tape"))]
+    fn finish<'de, E: de::Error>(
+        self,
+        fallthrough: Option<T>,
+        entries: Vec<(Content<'de>, Content<'de>)>,
+    ) -> Result<(T, Content<'de>), E> {
+        Ok((self.tag(fallthrough)?, Content::Map(entries)))
+    }
+}
+
+/// Whether `k` is a kind of map key that can be a tag.
+fn is_key(k: &Content) -> bool {
+    matches!(
+        k,
+        Content::String(_)
+            | Content::Str(_)
+            | Content::Bytes(_)
+            | Content::ByteBuf(_)
+            | Content::U8(_)
+            | Content::U16(_)
+            | Content::U32(_)
+            | Content::U64(_)
+    )
+}
+
+/// Whether a sequence is made of `[key, value]` pairs.
+fn is_pair_seq(items: &[Content]) -> bool {
+    !items.is_empty()
+        && items
+            .iter()
+            .all(|c| matches!(c, Content::Seq(pair) if pair.len() == 2))
+}
+
+/// Find the tag of a sequence read as the entries of a map, either a sequence
+/// of `[key, value]` pairs or a flat `[tag, value, key, value, ...]` starting
+/// with a tag. `None` if it has neither shape or holds no tag, in which case
+/// it is left as a sequence.
+fn seq_tag<'de, T, E>(items: &[Content<'de>]) -> Result<Option<T>, E>
+where
+    T: Deserialize<'de> + VariantTag,
+    E: de::Error,
+{
+    let mut search = TagSearch::new();
+    if is_pair_seq(items) {
+        for pair in items.iter().filter_map(Content::as_seq) {
+            search.entry::<E>(&pair[0], &pair[1]);
+        }
+    } else if items.len().is_multiple_of(2)
+        && items.first().is_some_and(|first| {
+            is_key(first) && T::deserialize(ContentRefDeserializer::<E>::new(first)).is_ok()
+        })
+    {
+        for entry in items.chunks_exact(2) {
+            search.entry::<E>(&entry[0], &entry[1]);
+        }
+    } else {
+        return Ok(None);
+    }
+    search.found()
+}
+
+/// Rebuild a sequence [`seq_tag`] found a tag in as the map it stands for.
+fn seq_entries<'de>(items: Vec<Content<'de>>) -> Content<'de> {
+    let entries = if is_pair_seq(&items) {
+        items
+            .into_iter()
+            .map(|pair| match pair {
+                Content::Seq(pair) => {
+                    let mut pair = pair.into_iter();
+                    (pair.next().unwrap(), pair.next().unwrap())
+                }
+                _ => unreachable!(),
+            })
+            .collect()
+    } else {
+        let mut items = items.into_iter();
+        let mut entries = Vec::with_capacity(items.len() / 2);
+        while let (Some(k), Some(v)) = (items.next(), items.next()) {
+            entries.push((k, v));
+        }
+        entries
+    };
+    Content::Map(entries)
+}
 
 pub struct TaggedContentVisitor<T> {
     expecting: &'static str,
     fallthrough: Option<T>,
+    accept_seq: bool,
 }
 
 impl<T> TaggedContentVisitor<T> {
@@ -18,119 +319,343 @@ impl<T> TaggedContentVisitor<T> {
         TaggedContentVisitor {
             expecting,
             fallthrough,
+            accept_seq: false,
+        }
+    }
+
+    /// Also accept the entries of the map as a sequence of `[key, value]`
+    /// pairs, or as a flat `[tag, value, key, value, ...]`.
+    pub fn accept_seq(mut self) -> Self {
+        self.accept_seq = true;
+        self
+    }
+}
+
+impl<T> TaggedContentVisitor<T> {
+    /// The fallthrough variant, or an error when there is none to accept a
+    /// value of this kind.
+    fn fallthrough_or<E: de::Error>(self, unexpected: Unexpected) -> Result<T, E> {
+        match self.fallthrough {
+            Some(default) => Ok(default),
+            None => Err(de::Error::invalid_type(unexpected, &self.expecting)),
+        }
+    }
+
+    /// Hand a value that cannot carry a tag to the fallthrough variant.
+    fn fall_through<'de, E: de::Error>(
+        self,
+        content: Content<'de>,
+    ) -> Result<(T, Buffered<'de>), E> {
+        let default = self.fallthrough_or(content.unexpected())?;
+        limits::charge(&content)?;
+        Ok((default, buffer(content)))
+    }
+}
+
+impl<T> TaggedContentVisitor<T> {
+    /// Find the tag of already buffered content, without copying it. Only
+    /// a sequence read as map entries with `accept_seq` is rebuilt as a map,
+    /// once a tag is found in it.
+    pub fn visit_content_ref<'a, 'de, E>(
+        self,
+        content: &'a Content<'de>,
+    ) -> Result<(T, Cow<'a, Content<'de>>), E>
+    where
+        T: Deserialize<'de> + VariantTag,
+        E: de::Error,
+    {
+        match content {
+            Content::Map(entries) => {
+                let mut search = TagSearch::new();
+                for (k, v) in entries {
+                    search.entry::<E>(k, v);
+                }
+                Ok((search.tag(self.fallthrough)?, Cow::Borrowed(content)))
+            }
+            Content::Seq(items) if self.accept_seq => match seq_tag::<T, E>(items)? {
+                Some(tag) => Ok((tag, Cow::Owned(seq_entries(items.clone())))),
+                None => Ok((
+                    self.fallthrough_or(Unexpected::Seq)?,
+                    Cow::Borrowed(content),
+                )),
+            },
+            _ => Ok((
+                self.fallthrough_or(content.unexpected())?,
+                Cow::Borrowed(content),
+            )),
         }
     }
 }
 
-impl<'de, T: Deserialize<'de>> Visitor<'de> for TaggedContentVisitor<T>
+impl<'de, T> Visitor<'de> for TaggedContentVisitor<T>
 where
-    T: Deserialize<'de>,
+    T: Deserialize<'de> + VariantTag,
 {
-    type Value = (T, Content<'de>);
+    type Value = (T, Buffered<'de>);
 
     fn expecting(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         fmt.write_str(self.expecting)
     }
 
-    // todo: add support for sequences?
-    // fn visit_seq<S>(self, mut seq: S) -> Result<Self::Value, S::Error>
-    // where
-    //     S: SeqAccess<'de>,
-    // {
-    //     let tag = match seq.next_element()? {
-    //         Some(tag) => tag,
-    //         None => {
-    //             return Err(de::Error::missing_field("blerhg".into()));
-    //         }
-    //     };
-    //     let rest = de::value::SeqAccessDeserializer::new(seq);
-    //     Ok((tag, Content::deserialize(rest)?))
-    // }
+    fn visit_bool<E>(self, v: bool) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        self.fall_through(Content::Bool(v))
+    }
 
-    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    fn visit_i8<E>(self, v: i8) -> Result<Self::Value, E>
     where
         E: de::Error,
     {
-        match self.fallthrough {
-            Some(default) => Ok((default, Content::String(v.into()))),
-            None => Err(de::Error::invalid_type(Unexpected::Str(v), &self.expecting)),
-        }
+        self.fall_through(Content::I8(v))
     }
 
-    fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+    fn visit_i16<E>(self, v: i16) -> Result<Self::Value, E>
     where
         E: de::Error,
     {
-        match self.fallthrough {
-            Some(default) => Ok((default, Content::U64(v))),
-            None => Err(de::Error::invalid_type(
-                Unexpected::Unsigned(v),
-                &self.expecting,
-            )),
-        }
+        self.fall_through(Content::I16(v))
+    }
+
+    fn visit_i32<E>(self, v: i32) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        self.fall_through(Content::I32(v))
     }
 
     fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
     where
         E: de::Error,
     {
-        match self.fallthrough {
-            Some(default) => Ok((default, Content::I64(v))),
-            None => Err(de::Error::invalid_type(
-                Unexpected::Signed(v),
-                &self.expecting,
-            )),
+        self.fall_through(Content::I64(v))
+    }
+
+    fn visit_i128<E>(self, v: i128) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        self.fall_through(Content::I128(v))
+    }
+
+    fn visit_u8<E>(self, v: u8) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        self.fall_through(Content::U8(v))
+    }
+
+    fn visit_u16<E>(self, v: u16) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        self.fall_through(Content::U16(v))
+    }
+
+    fn visit_u32<E>(self, v: u32) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        self.fall_through(Content::U32(v))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        self.fall_through(Content::U64(v))
+    }
+
+    fn visit_u128<E>(self, v: u128) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        self.fall_through(Content::U128(v))
+    }
+
+    fn visit_f32<E>(self, v: f32) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        self.fall_through(Content::F32(v))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        self.fall_through(Content::F64(v))
+    }
+
+    fn visit_char<E>(self, v: char) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        self.fall_through(Content::Char(v))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        limits::check_copy(v.len())?;
+        self.fall_through(Content::String(v.into()))
+    }
+
+    fn visit_borrowed_str<E>(self, v: &'de str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        self.fall_through(Content::Str(v))
+    }
+
+    fn visit_string<E>(self, v: String) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        self.fall_through(Content::String(v))
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        limits::check_copy(v.len())?;
+        self.fall_through(Content::ByteBuf(v.into()))
+    }
+
+    fn visit_borrowed_bytes<E>(self, v: &'de [u8]) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        self.fall_through(Content::Bytes(v))
+    }
+
+    fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        self.fall_through(Content::ByteBuf(v))
+    }
+
+    fn visit_unit<E>(self) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        self.fall_through(Content::Unit)
+    }
+
+    fn visit_none<E>(self) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        self.fall_through(Content::None)
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        let default = self.fallthrough_or(Unexpected::Option)?;
+        let content = ContentVisitor::new().visit_some(deserializer)?;
+        Ok((default, buffer(content)))
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        let default = self.fallthrough_or(Unexpected::NewtypeStruct)?;
+        let content = ContentVisitor::new().visit_newtype_struct(deserializer)?;
+        Ok((default, buffer(content)))
+    }
+
+    fn visit_seq<S>(self, seq: S) -> Result<Self::Value, S::Error>
+    where
+        S: SeqAccess<'de>,
+    {
+        if !self.accept_seq {
+            let default = self.fallthrough_or(Unexpected::Seq)?;
+            let content = ContentVisitor::new().visit_seq(seq)?;
+            return Ok((default, buffer(content)));
+        }
+        let Content::Seq(items) = ContentVisitor::new().visit_seq(seq)? else {
+            unreachable!()
+        };
+
+        match seq_tag::<T, S::Error>(&items)? {
+            Some(tag) => Ok((tag, buffer(seq_entries(items)))),
+            None => {
+                let default = self.fallthrough_or(Unexpected::Seq)?;
+                Ok((default, buffer(Content::Seq(items))))
+            }
         }
     }
 
+    #[cfg(not(feature = "tape"))]
     fn visit_map<M>(self, mut map: M) -> Result<Self::Value, M::Error>
     where
         M: MapAccess<'de>,
     {
-        let mut tag: Option<(T, String)> = None;
-        let mut vec = Vec::<(Content, Content)>::with_capacity(0); // todo
-        while let Some(k) = map.next_key()? {
-            match k {
-                Content::String(_) | Content::Str(_) | Content::Bytes(_) | Content::ByteBuf(_) => {
-                    match T::deserialize::<ContentDeserializer<'_, M::Error>>(
-                        k.clone().into_deserializer(),
-                    ) {
-                        // failed to parse a key, must be a non-tag field
-                        Err(_) => {
-                            let v = map.next_value()?;
-                            vec.push((k, v));
-                        }
-                        Ok(t) => {
-                            let v: Content = map.next_value()?;
-                            let key_name = k.as_str().unwrap_or("unknown");
-                            // Skip null values — they can't be a real tag
-                            if matches!(v, Content::None | Content::Unit) {
-                                vec.push((k, v));
-                            } else if let Some((_, prev_key)) = &tag {
-                                return Err(de::Error::custom(format_args!(
-                                    "found multiple implicit tag fields: `{prev_key}` and `{key_name}`",
-                                )));
-                            } else {
-                                tag = Some((t, key_name.to_owned()));
-                                vec.push((k, v));
-                            }
-                        }
-                    }
-                }
-                _ => {
-                    let v = map.next_value()?;
-                    vec.push((k, v));
-                }
-            };
+        let _depth = limits::enter_nested()?;
+        let mut search = TagSearch::new();
+        let mut entries =
+            Vec::<(Content, Content)>::with_capacity(limits::capacity(map.size_hint()));
+        while let Some((k, v)) = map.next_entry()? {
+            search.entry::<M::Error>(&k, &v);
+            entries.push((k, v));
+            limits::check_len(entries.len())?;
         }
-        match (tag, self.fallthrough) {
-            (None, None) => Err(de::Error::missing_field("tag was not found".into())),
-            (None, Some(default)) => Ok((default, Content::Map(vec))),
-            (Some((tag, _)), _) => Ok((tag, Content::Map(vec))),
+        search.finish(self.fallthrough, entries)
+    }
+
+    #[cfg(feature = "tape")]
+    fn visit_map<M>(self, mut map: M) -> Result<Self::Value, M::Error>
+    where
+        M: MapAccess<'de>,
+    {
+        let _depth = limits::enter_nested()?;
+        let mut search = TagSearch::new();
+        let mut tape = Tape::default();
+        let header = tape.begin_map(map.size_hint())?;
+        let mut len = 0;
+        while let Some(k) = map.next_key_seed(tape.seed())? {
+            let v = map.next_value_seed(tape.seed())?;
+            search.tape_entry::<M::Error>(&tape, k, v);
+            len += 1;
+            limits::check_len(len)?;
         }
+        tape.end_map(header, len);
+        Ok((search.tag(self.fallthrough)?, tape))
     }
 }
 
+/// Compare an input key to a name already lowercased by the derive,
+/// ignoring case and without allocating.
+pub fn eq_case_insensitive(key: &str, folded: &str) -> bool {
+    key.chars().flat_map(char::to_lowercase).eq(folded.chars())
+}
+
+/// Compare an input key to a name already folded by the derive, ignoring
+/// case, `_` and `-` so that `imageUrl`, `image_url` and `ImageURL` are equal.
+pub fn eq_normalized(key: &str, folded: &str) -> bool {
+    key.chars()
+        .filter(|c| !matches!(c, '_' | '-'))
+        .flat_map(char::to_lowercase)
+        .eq(folded.chars())
+}
+
+/// The keys of the input a fallthrough variant received, none of which
+/// matched a tag. Empty when the input was not a map.
+pub fn fallthrough_keys<'a>(content: &'a Content<'_>) -> impl Iterator<Item = &'a str> {
+    let entries = match content {
+        Content::Map(entries) => &entries[..],
+        _ => &[],
+    };
+    entries.iter().filter_map(|(k, _)| k.as_str())
+}
+
 /// Deserialize a missing field. If the field is `Option<T>` this returns
 /// `None`. For all other types, returns a "missing field" error. This
 /// replicates the same mechanism used by serde's own derive macro.
@@ -172,26 +697,122 @@ where
     Deserialize::deserialize(deserializer)
 }
 
-pub fn extract_at_index<'de, E: serde::de::Error>(
-    c: Content<'de>,
-    index: usize,
-) -> ::std::result::Result<(Content<'de>, Option<Content<'de>>), E> {
-    match c {
-        Content::Seq(mut s) => {
-            if s.len() == 0 {
-                Err(E::missing_field("missing tag: sequence is empty"))
-            } else if index >= s.len() {
-                Err(E::missing_field("tag index out of bounds"))
-            } else {
-                Ok((s.remove(index), Some(Content::Seq(s))))
-            }
+/// Why a tuple variant's trial rejected the input.
+enum Rejection<E> {
+    /// The input was not a sequence with the variant's number of fields.
+    Length { expected: usize },
+    /// The value at the tag position failed to deserialize.
+    Tag { path: &'static str, error: E },
+    /// The `tag_with` predicate rejected the value at the tag position.
+    Predicate {
+        path: &'static str,
+        predicate: &'static str,
+    },
+    /// There was no value at the (nested) tag position.
+    MissingTag { path: &'static str },
+    /// The variant failed to deserialize from the whole input, as flatten
+    /// variants and one-field variants given a non-sequence do.
+    Whole(E),
+}
+
+/// Records the trials that failed while deserializing a tuple enum, or the
+/// fallthrough variants of a struct enum, so that if no variant commits the
+/// error can explain why each one was rejected.
+pub struct TrialErrors<E> {
+    ty_name: &'static str,
+    rejections: Vec<(&'static str, Rejection<E>)>,
+}
+
+impl<E: de::Error> TrialErrors<E> {
+    pub fn new(ty_name: &'static str) -> Self {
+        TrialErrors {
+            ty_name,
+            rejections: Vec::new(),
         }
-        c => {
-            if index == 0 {
-                Ok((c, None))
-            } else {
-                Err(E::missing_field("tag index out of bounds for non-sequence"))
-            }
+    }
+
+    pub fn length(&mut self, variant: &'static str, expected: usize) {
+        self.rejections
+            .push((variant, Rejection::Length { expected }));
+    }
+
+    pub fn tag(&mut self, variant: &'static str, path: &'static str, error: E) {
+        self.rejections
+            .push((variant, Rejection::Tag { path, error }));
+    }
+
+    pub fn predicate(
+        &mut self,
+        variant: &'static str,
+        path: &'static str,
+        predicate: &'static str,
+    ) {
+        self.rejections
+            .push((variant, Rejection::Predicate { path, predicate }));
+    }
+
+    pub fn missing_tag(&mut self, variant: &'static str, path: &'static str) {
+        self.rejections
+            .push((variant, Rejection::MissingTag { path }));
+    }
+
+    pub fn whole(&mut self, variant: &'static str, error: E) {
+        self.rejections.push((variant, Rejection::Whole(error)));
+    }
+
+    /// Build the final error, describing `content` where a variant rejected
+    /// it because of its shape.
+    pub fn finish(self, content: &Content) -> E {
+        self.message(Some(content))
+    }
+
+    /// Build the final error of fallthrough variants, which only reject the
+    /// input as a whole.
+    pub fn finish_whole(self) -> E {
+        self.message(None)
+    }
+
+    fn message(self, content: Option<&Content>) -> E {
+        use std::fmt::Write;
+
+        let mut msg = format!("data did not match any variant of enum {}", self.ty_name);
+
+        for (i, (variant, rejection)) in self.rejections.iter().enumerate() {
+            let sep = if i == 0 { ": " } else { "; " };
+            let _ = write!(msg, "{sep}{}::{variant}: ", self.ty_name);
+
+            let _ = match rejection {
+                Rejection::Length { expected } => match content {
+                    Some(Content::Seq(seq)) => write!(
+                        msg,
+                        "expected a sequence of {expected} element(s), found {}",
+                        seq.len()
+                    ),
+                    Some(other) => write!(
+                        msg,
+                        "expected a sequence of {expected} element(s), found {}",
+                        other.unexpected()
+                    ),
+                    None => write!(msg, "expected a sequence of {expected} element(s)"),
+                },
+                Rejection::Tag { path, error } => write!(msg, "tag at index {path}: {error}"),
+                Rejection::Predicate { path, predicate } => {
+                    write!(msg, "tag at index {path} rejected by `{predicate}`")
+                }
+                Rejection::MissingTag { path } => write!(msg, "no value at tag index {path}"),
+                Rejection::Whole(error) => write!(msg, "{error}"),
+            };
         }
+
+        E::custom(msg)
     }
 }
+
+/// Follow `path` through nested sequences, returning the content found at
+/// the end or `None` if an index is out of bounds or lands on a non-sequence.
+pub fn content_at_path<'a, 'de>(c: &'a Content<'de>, path: &[usize]) -> Option<&'a Content<'de>> {
+    path.iter().try_fold(c, |c, &index| match c {
+        Content::Seq(s) => s.get(index),
+        _ => None,
+    })
+}
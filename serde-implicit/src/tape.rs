@@ -0,0 +1,1351 @@
+//! A flat alternative to [`Content`] for buffering input, enabled by the
+//! `tape` feature.
+//!
+//! [`Content`] gives every sequence and map its own `Vec`, and every string
+//! copied out of the input its own `String`. A [`Tape`] records the input as
+//! a single list of tokens instead, a sequence or map being a header followed
+//! by its elements, and copies strings and bytes end to end into two shared
+//! buffers. Buffering an input then takes a few allocations however many
+//! values it holds.
+//!
+//! With the feature enabled, struct enums buffer their input into a `Tape`
+//! while looking for the tag and replay it into the variant from there.
+//! `tag_with` predicates, fallthrough variants taking `Content` and
+//! [`FromContentRef`](crate::FromContentRef) still see [`Content`].
+
+use std::fmt;
+use std::marker::PhantomData;
+
+use serde::de::{
+    self, Deserialize, DeserializeSeed, Deserializer, EnumAccess, Expected, MapAccess, SeqAccess,
+    Unexpected, Visitor,
+};
+
+use crate::content::{self, Content, NUMBER_TOKEN, RAW_VALUE_TOKEN};
+use crate::limits;
+
+/// One value, or the start of one, on a [`Tape`].
+#[derive(Debug, Clone, Copy)]
+enum Token<'de> {
+    Bool(bool),
+
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+    /// The high and low halves of a `u128`, which would otherwise double the
+    /// alignment, and so the size, of every token.
+    U128(u64, u64),
+
+    I8(i8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    /// The bits of an `i128`, split like `U128`.
+    I128(u64, u64),
+
+    F32(f32),
+    F64(f64),
+
+    Char(char),
+    /// A string copied to `strings[start..end]`.
+    String(usize, usize),
+    Str(&'de str),
+    /// Bytes copied to `bytes[start..end]`.
+    ByteBuf(usize, usize),
+    Bytes(&'de [u8]),
+
+    None,
+    /// Followed by the value, which ends before the token at `end`.
+    Some {
+        end: usize,
+    },
+
+    Unit,
+    /// Followed by the value, which ends before the token at `end`.
+    Newtype {
+        end: usize,
+    },
+    /// Followed by `len` elements, which end before the token at `end`.
+    Seq {
+        len: usize,
+        end: usize,
+    },
+    /// Followed by `len` keys and values, alternating, which end before the
+    /// token at `end`.
+    Map {
+        len: usize,
+        end: usize,
+    },
+}
+
+/// A value buffered as a flat list of tokens.
+///
+/// Holds the same values as [`Content`], which it converts from and to, and
+/// is read back with [`Tape::deserializer`].
+#[derive(Debug, Clone, Default)]
+pub struct Tape<'de> {
+    tokens: Vec<Token<'de>>,
+    strings: String,
+    bytes: Vec<u8>,
+}
+
+impl<'de> Tape<'de> {
+    /// A deserializer for the buffered value.
+    pub fn deserializer<E>(&self) -> TapeRefDeserializer<'_, 'de, E> {
+        TapeRefDeserializer::new(self, 0)
+    }
+
+    /// The buffered value as [`Content`].
+    pub fn to_content(&self) -> Content<'de> {
+        self.content_at(0)
+    }
+
+    fn content_at(&self, pos: usize) -> Content<'de> {
+        match self.tokens[pos] {
+            Token::Bool(b) => Content::Bool(b),
+            Token::U8(n) => Content::U8(n),
+            Token::U16(n) => Content::U16(n),
+            Token::U32(n) => Content::U32(n),
+            Token::U64(n) => Content::U64(n),
+            Token::U128(hi, lo) => Content::U128(join(hi, lo)),
+            Token::I8(n) => Content::I8(n),
+            Token::I16(n) => Content::I16(n),
+            Token::I32(n) => Content::I32(n),
+            Token::I64(n) => Content::I64(n),
+            Token::I128(hi, lo) => Content::I128(join(hi, lo) as i128),
+            Token::F32(f) => Content::F32(f),
+            Token::F64(f) => Content::F64(f),
+            Token::Char(c) => Content::Char(c),
+            Token::String(start, end) => Content::String(self.strings[start..end].to_owned()),
+            Token::Str(s) => Content::Str(s),
+            Token::ByteBuf(start, end) => Content::ByteBuf(self.bytes[start..end].to_owned()),
+            Token::Bytes(b) => Content::Bytes(b),
+            Token::None => Content::None,
+            Token::Some { .. } => Content::Some(Box::new(self.content_at(pos + 1))),
+            Token::Unit => Content::Unit,
+            Token::Newtype { .. } => Content::Newtype(Box::new(self.content_at(pos + 1))),
+            Token::Seq { len, .. } => Content::Seq(
+                self.elements(pos, len)
+                    .map(|item| self.content_at(item))
+                    .collect(),
+            ),
+            Token::Map { len, .. } => {
+                let mut entries = Vec::with_capacity(len);
+                let mut items = self.elements(pos, len * 2);
+                while let (Some(k), Some(v)) = (items.next(), items.next()) {
+                    entries.push((self.content_at(k), self.content_at(v)));
+                }
+                Content::Map(entries)
+            }
+        }
+    }
+
+    /// The position just past the value at `pos`.
+    fn skip(&self, pos: usize) -> usize {
+        match self.tokens[pos] {
+            Token::Some { end }
+            | Token::Newtype { end }
+            | Token::Seq { end, .. }
+            | Token::Map { end, .. } => end,
+            _ => pos + 1,
+        }
+    }
+
+    /// The positions of the `count` values following the header at `pos`.
+    fn elements(&self, pos: usize, count: usize) -> impl Iterator<Item = usize> + '_ {
+        let mut next = pos + 1;
+        (0..count).map(move |_| {
+            let item = next;
+            next = self.skip(item);
+            item
+        })
+    }
+
+    fn str_at(&self, pos: usize) -> Option<&str> {
+        match self.tokens[pos] {
+            Token::String(start, end) => Some(&self.strings[start..end]),
+            Token::Str(s) => Some(s),
+            Token::ByteBuf(start, end) => str::from_utf8(&self.bytes[start..end]).ok(),
+            Token::Bytes(b) => str::from_utf8(b).ok(),
+            _ => None,
+        }
+    }
+
+    /// The digits of an arbitrary precision number `serde_json` passed as a
+    /// map with a single entry, see [`Content`].
+    fn number_str(&self, pos: usize) -> Option<&str> {
+        match self.tokens[pos] {
+            Token::Map { len: 1, .. } if self.str_at(pos + 1) == Some(NUMBER_TOKEN) => {
+                self.str_at(pos + 2)
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the value at `pos` is a kind of map key that can be a tag.
+    pub(crate) fn is_key(&self, pos: usize) -> bool {
+        matches!(
+            self.tokens[pos],
+            Token::String(..)
+                | Token::Str(_)
+                | Token::ByteBuf(..)
+                | Token::Bytes(_)
+                | Token::U8(_)
+                | Token::U16(_)
+                | Token::U32(_)
+                | Token::U64(_)
+        )
+    }
+
+    /// Name the map key at `pos` in error messages.
+    pub(crate) fn key_name(&self, pos: usize) -> String {
+        match self.tokens[pos] {
+            Token::U8(n) => n.to_string(),
+            Token::U16(n) => n.to_string(),
+            Token::U32(n) => n.to_string(),
+            Token::U64(n) => n.to_string(),
+            _ => self.str_at(pos).unwrap_or("unknown").to_owned(),
+        }
+    }
+
+    /// Whether the value at `pos` is `None` or `Unit`.
+    pub(crate) fn is_null(&self, pos: usize) -> bool {
+        matches!(self.tokens[pos], Token::None | Token::Unit)
+    }
+
+    /// The string keys of the buffered value, if it is a map.
+    pub(crate) fn keys(&self) -> impl Iterator<Item = &str> {
+        let len = match self.tokens.first() {
+            Some(Token::Map { len, .. }) => *len,
+            _ => 0,
+        };
+        self.elements(0, len * 2)
+            .step_by(2)
+            .filter_map(|k| self.str_at(k))
+    }
+
+    #[cold]
+    fn unexpected(&self, pos: usize) -> Unexpected<'_> {
+        match self.tokens[pos] {
+            Token::Bool(b) => Unexpected::Bool(b),
+            Token::U8(n) => Unexpected::Unsigned(n as u64),
+            Token::U16(n) => Unexpected::Unsigned(n as u64),
+            Token::U32(n) => Unexpected::Unsigned(n as u64),
+            Token::U64(n) => Unexpected::Unsigned(n),
+            Token::U128(hi, lo) => {
+                u64::try_from(join(hi, lo)).map_or(Unexpected::Other("u128"), Unexpected::Unsigned)
+            }
+            Token::I8(n) => Unexpected::Signed(n as i64),
+            Token::I16(n) => Unexpected::Signed(n as i64),
+            Token::I32(n) => Unexpected::Signed(n as i64),
+            Token::I64(n) => Unexpected::Signed(n),
+            Token::I128(hi, lo) => i64::try_from(join(hi, lo) as i128)
+                .map_or(Unexpected::Other("i128"), Unexpected::Signed),
+            Token::F32(f) => Unexpected::Float(f as f64),
+            Token::F64(f) => Unexpected::Float(f),
+            Token::Char(c) => Unexpected::Char(c),
+            Token::String(start, end) => Unexpected::Str(&self.strings[start..end]),
+            Token::Str(s) => Unexpected::Str(s),
+            Token::ByteBuf(start, end) => Unexpected::Bytes(&self.bytes[start..end]),
+            Token::Bytes(b) => Unexpected::Bytes(b),
+            Token::None | Token::Some { .. } => Unexpected::Option,
+            Token::Unit => Unexpected::Unit,
+            Token::Newtype { .. } => Unexpected::NewtypeStruct,
+            Token::Seq { .. } => Unexpected::Seq,
+            Token::Map { .. } => Unexpected::Map,
+        }
+    }
+
+    /// Append a token, counting it against the byte limit. It is counted as
+    /// the `Content` it stands for, so that `max_bytes` accepts the same
+    /// inputs with or without the `tape` feature.
+    fn push<E: de::Error>(&mut self, token: Token<'de>, owned: usize) -> Result<usize, E> {
+        limits::charge_bytes(size_of::<Content>() + owned)?;
+        self.tokens.push(token);
+        Ok(self.tokens.len() - 1)
+    }
+
+    fn push_string<E: de::Error>(&mut self, s: &str) -> Result<usize, E> {
+        limits::check_copy(s.len())?;
+        let start = self.strings.len();
+        self.strings.push_str(s);
+        self.push(Token::String(start, self.strings.len()), s.len())
+    }
+
+    fn push_bytes<E: de::Error>(&mut self, b: &[u8]) -> Result<usize, E> {
+        limits::check_copy(b.len())?;
+        let start = self.bytes.len();
+        self.bytes.extend_from_slice(b);
+        self.push(Token::ByteBuf(start, self.bytes.len()), b.len())
+    }
+
+    /// Set the end, and length, of the header at `pos` to what follows it.
+    fn close(&mut self, pos: usize, count: usize) {
+        let end = self.tokens.len();
+        match &mut self.tokens[pos] {
+            Token::Some { end: e } | Token::Newtype { end: e } => *e = end,
+            Token::Seq { len, end: e } | Token::Map { len, end: e } => {
+                *len = count;
+                *e = end;
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Append a value read from `deserializer`, returning its position.
+    pub(crate) fn read<D>(&mut self, deserializer: D) -> Result<usize, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(TapeVisitor(self))
+    }
+
+    /// A seed appending the value it reads, returning its position.
+    pub(crate) fn seed(&mut self) -> impl DeserializeSeed<'de, Value = usize> + '_ {
+        TapeVisitor(self)
+    }
+
+    /// Start buffering a map, returning the position of its header.
+    pub(crate) fn begin_map<E: de::Error>(&mut self, hint: Option<usize>) -> Result<usize, E> {
+        self.tokens
+            .reserve(limits::capacity(hint).saturating_mul(2));
+        self.push(Token::Map { len: 0, end: 0 }, 0)
+    }
+
+    /// Finish the map started at `pos`, holding `len` entries.
+    pub(crate) fn end_map(&mut self, pos: usize, len: usize) {
+        self.close(pos, len);
+    }
+
+    fn push_content(&mut self, content: Content<'de>) {
+        let pos = self.tokens.len();
+        let token = match content {
+            Content::Bool(b) => Token::Bool(b),
+            Content::U8(n) => Token::U8(n),
+            Content::U16(n) => Token::U16(n),
+            Content::U32(n) => Token::U32(n),
+            Content::U64(n) => Token::U64(n),
+            Content::U128(n) => split(n),
+            Content::I8(n) => Token::I8(n),
+            Content::I16(n) => Token::I16(n),
+            Content::I32(n) => Token::I32(n),
+            Content::I64(n) => Token::I64(n),
+            Content::I128(n) => split_signed(n),
+            Content::F32(f) => Token::F32(f),
+            Content::F64(f) => Token::F64(f),
+            Content::Char(c) => Token::Char(c),
+            Content::String(s) => {
+                let start = self.strings.len();
+                self.strings.push_str(&s);
+                Token::String(start, self.strings.len())
+            }
+            Content::Str(s) => Token::Str(s),
+            Content::ByteBuf(b) => {
+                let start = self.bytes.len();
+                self.bytes.extend_from_slice(&b);
+                Token::ByteBuf(start, self.bytes.len())
+            }
+            Content::Bytes(b) => Token::Bytes(b),
+            Content::None => Token::None,
+            Content::Unit => Token::Unit,
+            Content::Some(c) => {
+                self.tokens.push(Token::Some { end: 0 });
+                self.push_content(*c);
+                self.close(pos, 1);
+                return;
+            }
+            Content::Newtype(c) => {
+                self.tokens.push(Token::Newtype { end: 0 });
+                self.push_content(*c);
+                self.close(pos, 1);
+                return;
+            }
+            Content::Seq(items) => {
+                let len = items.len();
+                self.tokens.push(Token::Seq { len: 0, end: 0 });
+                for item in items {
+                    self.push_content(item);
+                }
+                self.close(pos, len);
+                return;
+            }
+            Content::Map(entries) => {
+                let len = entries.len();
+                self.tokens.push(Token::Map { len: 0, end: 0 });
+                for (k, v) in entries {
+                    self.push_content(k);
+                    self.push_content(v);
+                }
+                self.close(pos, len);
+                return;
+            }
+        };
+        self.tokens.push(token);
+    }
+}
+
+fn split<'de>(n: u128) -> Token<'de> {
+    Token::U128((n >> 64) as u64, n as u64)
+}
+
+fn split_signed<'de>(n: i128) -> Token<'de> {
+    let n = n as u128;
+    Token::I128((n >> 64) as u64, n as u64)
+}
+
+fn join(hi: u64, lo: u64) -> u128 {
+    (hi as u128) << 64 | lo as u128
+}
+
+impl<'de> From<Content<'de>> for Tape<'de> {
+    fn from(content: Content<'de>) -> Self {
+        let mut tape = Tape::default();
+        tape.push_content(content);
+        tape
+    }
+}
+
+impl<'de> Deserialize<'de> for Tape<'de> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let mut tape = Tape::default();
+        tape.read(deserializer)?;
+        Ok(tape)
+    }
+}
+
+/// Appends the value it visits to a tape, returning its position.
+struct TapeVisitor<'t, 'de>(&'t mut Tape<'de>);
+
+impl<'de> DeserializeSeed<'de> for TapeVisitor<'_, 'de> {
+    type Value = usize;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        self.0.read(deserializer)
+    }
+}
+
+macro_rules! visit_token {
+    ($($visit:ident($ty:ty) => $token:ident,)*) => {
+        $(
+            fn $visit<E>(self, value: $ty) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                self.0.push(Token::$token(value), 0)
+            }
+        )*
+    };
+}
+
+impl<'de> Visitor<'de> for TapeVisitor<'_, 'de> {
+    type Value = usize;
+
+    fn expecting(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str("any value")
+    }
+
+    visit_token! {
+        visit_bool(bool) => Bool,
+        visit_i8(i8) => I8,
+        visit_i16(i16) => I16,
+        visit_i32(i32) => I32,
+        visit_i64(i64) => I64,
+        visit_u8(u8) => U8,
+        visit_u16(u16) => U16,
+        visit_u32(u32) => U32,
+        visit_u64(u64) => U64,
+        visit_f32(f32) => F32,
+        visit_f64(f64) => F64,
+        visit_char(char) => Char,
+        visit_borrowed_str(&'de str) => Str,
+        visit_borrowed_bytes(&'de [u8]) => Bytes,
+    }
+
+    fn visit_u128<E>(self, value: u128) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        self.0.push(split(value), 0)
+    }
+
+    fn visit_i128<E>(self, value: i128) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        self.0.push(split_signed(value), 0)
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        self.0.push_string(value)
+    }
+
+    fn visit_string<E>(self, value: String) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        self.0.push_string(&value)
+    }
+
+    fn visit_bytes<E>(self, value: &[u8]) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        self.0.push_bytes(value)
+    }
+
+    fn visit_byte_buf<E>(self, value: Vec<u8>) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        self.0.push_bytes(&value)
+    }
+
+    fn visit_unit<E>(self) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        self.0.push(Token::Unit, 0)
+    }
+
+    fn visit_none<E>(self) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        self.0.push(Token::None, 0)
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let _depth = limits::enter_nested()?;
+        let pos = self.0.push(Token::Some { end: 0 }, 0)?;
+        self.0.read(deserializer)?;
+        self.0.close(pos, 1);
+        Ok(pos)
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let _depth = limits::enter_nested()?;
+        let pos = self.0.push(Token::Newtype { end: 0 }, 0)?;
+        self.0.read(deserializer)?;
+        self.0.close(pos, 1);
+        Ok(pos)
+    }
+
+    fn visit_seq<V>(self, mut visitor: V) -> Result<Self::Value, V::Error>
+    where
+        V: SeqAccess<'de>,
+    {
+        let _depth = limits::enter_nested()?;
+        self.0.tokens.reserve(limits::capacity(visitor.size_hint()));
+        let pos = self.0.push(Token::Seq { len: 0, end: 0 }, 0)?;
+        let mut len = 0;
+        while visitor.next_element_seed(TapeVisitor(self.0))?.is_some() {
+            len += 1;
+            limits::check_len(len)?;
+        }
+        self.0.close(pos, len);
+        Ok(pos)
+    }
+
+    fn visit_map<V>(self, mut visitor: V) -> Result<Self::Value, V::Error>
+    where
+        V: MapAccess<'de>,
+    {
+        let _depth = limits::enter_nested()?;
+        let pos = self.0.begin_map(visitor.size_hint())?;
+        let mut len = 0;
+        while visitor.next_key_seed(TapeVisitor(self.0))?.is_some() {
+            visitor.next_value_seed(TapeVisitor(self.0))?;
+            len += 1;
+            limits::check_len(len)?;
+        }
+        self.0.end_map(pos, len);
+        Ok(pos)
+    }
+
+    fn visit_enum<V>(self, _visitor: V) -> Result<Self::Value, V::Error>
+    where
+        V: EnumAccess<'de>,
+    {
+        Err(de::Error::custom(
+            "untagged and internally tagged enums do not support enum input",
+        ))
+    }
+}
+
+/// Replays a value owned by a [`Tape`], for generated code.
+pub struct TapeDeserializer<'de, E> {
+    tape: Tape<'de>,
+    err: PhantomData<E>,
+}
+
+impl<'de, E> TapeDeserializer<'de, E> {
+    pub fn new(tape: Tape<'de>) -> Self {
+        TapeDeserializer {
+            tape,
+            err: PhantomData,
+        }
+    }
+}
+
+macro_rules! forward_to_ref {
+    ($($method:ident($($arg:ident: $ty:ty),*),)*) => {
+        $(
+            fn $method<V>(self, $($arg: $ty,)* visitor: V) -> Result<V::Value, E>
+            where
+                V: Visitor<'de>,
+            {
+                self.tape.deserializer().$method($($arg,)* visitor)
+            }
+        )*
+    };
+}
+
+impl<'de, E> Deserializer<'de> for TapeDeserializer<'de, E>
+where
+    E: de::Error,
+{
+    type Error = E;
+
+    forward_to_ref! {
+        deserialize_any(),
+        deserialize_bool(),
+        deserialize_i8(),
+        deserialize_i16(),
+        deserialize_i32(),
+        deserialize_i64(),
+        deserialize_i128(),
+        deserialize_u8(),
+        deserialize_u16(),
+        deserialize_u32(),
+        deserialize_u64(),
+        deserialize_u128(),
+        deserialize_f32(),
+        deserialize_f64(),
+        deserialize_char(),
+        deserialize_str(),
+        deserialize_string(),
+        deserialize_bytes(),
+        deserialize_byte_buf(),
+        deserialize_option(),
+        deserialize_unit(),
+        deserialize_unit_struct(name: &'static str),
+        deserialize_newtype_struct(name: &'static str),
+        deserialize_seq(),
+        deserialize_tuple(len: usize),
+        deserialize_tuple_struct(name: &'static str, len: usize),
+        deserialize_map(),
+        deserialize_struct(name: &'static str, fields: &'static [&'static str]),
+        deserialize_enum(name: &'static str, variants: &'static [&'static str]),
+        deserialize_identifier(),
+        deserialize_ignored_any(),
+    }
+}
+
+/// Replays the value at one position of a [`Tape`], which it borrows.
+pub struct TapeRefDeserializer<'a, 'de, E> {
+    tape: &'a Tape<'de>,
+    pos: usize,
+    err: PhantomData<E>,
+}
+
+impl<'a, 'de, E> TapeRefDeserializer<'a, 'de, E> {
+    pub(crate) fn new(tape: &'a Tape<'de>, pos: usize) -> Self {
+        TapeRefDeserializer {
+            tape,
+            pos,
+            err: PhantomData,
+        }
+    }
+}
+
+impl<E> Copy for TapeRefDeserializer<'_, '_, E> {}
+
+impl<E> Clone for TapeRefDeserializer<'_, '_, E> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, 'de, E> TapeRefDeserializer<'a, 'de, E>
+where
+    E: de::Error,
+{
+    fn token(&self) -> Token<'de> {
+        self.tape.tokens[self.pos]
+    }
+
+    /// The deserializer for the value following the header of this one.
+    fn inner(self) -> Self {
+        TapeRefDeserializer::new(self.tape, self.pos + 1)
+    }
+
+    #[cold]
+    fn invalid_type(self, exp: &dyn Expected) -> E {
+        de::Error::invalid_type(self.tape.unexpected(self.pos), exp)
+    }
+
+    fn deserialize_integer<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        match self.token() {
+            Token::U8(v) => visitor.visit_u8(v),
+            Token::U16(v) => visitor.visit_u16(v),
+            Token::U32(v) => visitor.visit_u32(v),
+            Token::U64(v) => visitor.visit_u64(v),
+            Token::U128(hi, lo) => visitor.visit_u128(join(hi, lo)),
+            Token::I8(v) => visitor.visit_i8(v),
+            Token::I16(v) => visitor.visit_i16(v),
+            Token::I32(v) => visitor.visit_i32(v),
+            Token::I64(v) => visitor.visit_i64(v),
+            Token::I128(hi, lo) => visitor.visit_i128(join(hi, lo) as i128),
+            _ => match self.tape.number_str(self.pos) {
+                Some(digits) => content::visit_number_str(digits, visitor),
+                None => Err(self.invalid_type(&visitor)),
+            },
+        }
+    }
+
+    fn deserialize_float<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        match self.token() {
+            Token::F32(v) => visitor.visit_f32(v),
+            Token::F64(v) => visitor.visit_f64(v),
+            _ => self.deserialize_integer(visitor),
+        }
+    }
+
+    /// Visit the value as a string or bytes, or else call `other`.
+    fn visit_str_or<V>(
+        self,
+        visitor: V,
+        other: impl FnOnce(Self, V) -> Result<V::Value, E>,
+    ) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        match self.token() {
+            Token::String(start, end) => visitor.visit_str(&self.tape.strings[start..end]),
+            Token::Str(v) => visitor.visit_borrowed_str(v),
+            Token::ByteBuf(start, end) => visitor.visit_bytes(&self.tape.bytes[start..end]),
+            Token::Bytes(v) => visitor.visit_borrowed_bytes(v),
+            _ => other(self, visitor),
+        }
+    }
+
+    fn visit_seq<V>(self, len: usize, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        let mut seq = TapeSeqAccess {
+            tape: self.tape,
+            items: self.tape.elements(self.pos, len),
+            remaining: len,
+            err: PhantomData,
+        };
+        let value = visitor.visit_seq(&mut seq)?;
+        match seq.remaining {
+            0 => Ok(value),
+            rest => Err(de::Error::invalid_length(
+                len,
+                &ExpectedInSeq(len - rest, "sequence"),
+            )),
+        }
+    }
+
+    fn visit_map<V>(self, len: usize, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        let mut map = TapeMapAccess {
+            tape: self.tape,
+            items: self.tape.elements(self.pos, len * 2),
+            remaining: len,
+            err: PhantomData,
+        };
+        let value = visitor.visit_map(&mut map)?;
+        match map.remaining {
+            0 => Ok(value),
+            rest => Err(de::Error::invalid_length(
+                len,
+                &ExpectedInSeq(len - rest, "map"),
+            )),
+        }
+    }
+}
+
+impl<'de, E> Deserializer<'de> for TapeRefDeserializer<'_, 'de, E>
+where
+    E: de::Error,
+{
+    type Error = E;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        match self.token() {
+            Token::Bool(v) => visitor.visit_bool(v),
+            Token::Char(v) => visitor.visit_char(v),
+            Token::F32(_) | Token::F64(_) => self.deserialize_float(visitor),
+            Token::String(..) | Token::Str(_) | Token::ByteBuf(..) | Token::Bytes(_) => {
+                self.visit_str_or(visitor, |_, _| unreachable!())
+            }
+            Token::Unit => visitor.visit_unit(),
+            Token::None => visitor.visit_none(),
+            Token::Some { .. } => visitor.visit_some(self.inner()),
+            Token::Newtype { .. } => visitor.visit_newtype_struct(self.inner()),
+            Token::Seq { len, .. } => self.visit_seq(len, visitor),
+            Token::Map { len, .. } => self.visit_map(len, visitor),
+            _ => self.deserialize_integer(visitor),
+        }
+    }
+
+    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        match self.token() {
+            Token::Bool(v) => visitor.visit_bool(v),
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_i8<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_integer(visitor)
+    }
+
+    fn deserialize_i16<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_integer(visitor)
+    }
+
+    fn deserialize_i32<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_integer(visitor)
+    }
+
+    fn deserialize_i64<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_integer(visitor)
+    }
+
+    fn deserialize_i128<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_integer(visitor)
+    }
+
+    fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_integer(visitor)
+    }
+
+    fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_integer(visitor)
+    }
+
+    fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_integer(visitor)
+    }
+
+    fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_integer(visitor)
+    }
+
+    fn deserialize_u128<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_integer(visitor)
+    }
+
+    fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_float(visitor)
+    }
+
+    fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_float(visitor)
+    }
+
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        match self.token() {
+            Token::Char(v) => visitor.visit_char(v),
+            Token::String(start, end) => visitor.visit_str(&self.tape.strings[start..end]),
+            Token::Str(v) => visitor.visit_borrowed_str(v),
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        self.visit_str_or(visitor, |de, visitor| Err(de.invalid_type(&visitor)))
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        self.visit_str_or(visitor, |de, visitor| match de.token() {
+            Token::Seq { len, .. } => de.visit_seq(len, visitor),
+            _ => Err(de.invalid_type(&visitor)),
+        })
+    }
+
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        match self.token() {
+            Token::None => visitor.visit_none(),
+            Token::Some { .. } => visitor.visit_some(self.inner()),
+            Token::Unit => visitor.visit_unit(),
+            // Formats like JSON never call `visit_some`, see
+            // `ContentRefDeserializer::deserialize_option`
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        match self.token() {
+            Token::Unit => visitor.visit_unit(),
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V>(self, name: &str, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        match self.token() {
+            Token::Newtype { .. } => visitor.visit_newtype_struct(self.inner()),
+            _ if name == RAW_VALUE_TOKEN => {
+                content::visit_raw_value(&self.tape.content_at(self.pos), visitor)
+            }
+            // Formats like JSON never call `visit_newtype_struct`, see
+            // `ContentRefDeserializer::deserialize_newtype_struct`
+            _ => visitor.visit_newtype_struct(self),
+        }
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        match self.token() {
+            Token::Seq { len, .. } => self.visit_seq(len, visitor),
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        match self.token() {
+            Token::Map { len, .. } => self.visit_map(len, visitor),
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        match self.token() {
+            Token::Seq { len, .. } => self.visit_seq(len, visitor),
+            Token::Map { len, .. } => self.visit_map(len, visitor),
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        let (variant, value) = match self.token() {
+            // enums are encoded in json as maps with a single key:value pair
+            Token::Map { len: 1, .. } => (self.inner(), Some(self.tape.skip(self.pos + 1))),
+            Token::Map { .. } => {
+                return Err(de::Error::invalid_value(
+                    Unexpected::Map,
+                    &"map with a single key",
+                ));
+            }
+            Token::String(..) | Token::Str(_) => (self, None),
+            _ => {
+                return Err(de::Error::invalid_type(
+                    self.tape.unexpected(self.pos),
+                    &"string or map",
+                ));
+            }
+        };
+
+        visitor.visit_enum(TapeEnumAccess {
+            variant,
+            value: value.map(|pos| TapeRefDeserializer::new(self.tape, pos)),
+        })
+    }
+
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        self.visit_str_or(visitor, |de, visitor| match de.token() {
+            Token::U8(v) => visitor.visit_u8(v),
+            Token::U16(v) => visitor.visit_u16(v),
+            Token::U32(v) => visitor.visit_u32(v),
+            Token::U64(v) => visitor.visit_u64(v),
+            _ => Err(de.invalid_type(&visitor)),
+        })
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+}
+
+/// `"{n} elements in {kind}"`, as the length expected by a visitor that
+/// stopped early.
+struct ExpectedInSeq(usize, &'static str);
+
+impl Expected for ExpectedInSeq {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            1 => write!(formatter, "1 element in {}", self.1),
+            n => write!(formatter, "{n} elements in {}", self.1),
+        }
+    }
+}
+
+struct TapeSeqAccess<'a, 'de, I, E> {
+    tape: &'a Tape<'de>,
+    items: I,
+    remaining: usize,
+    err: PhantomData<E>,
+}
+
+impl<'de, I, E> SeqAccess<'de> for TapeSeqAccess<'_, 'de, I, E>
+where
+    I: Iterator<Item = usize>,
+    E: de::Error,
+{
+    type Error = E;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, E>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self.items.next() {
+            Some(pos) => {
+                self.remaining -= 1;
+                seed.deserialize(TapeRefDeserializer::new(self.tape, pos))
+                    .map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+struct TapeMapAccess<'a, 'de, I, E> {
+    tape: &'a Tape<'de>,
+    items: I,
+    remaining: usize,
+    err: PhantomData<E>,
+}
+
+impl<'de, I, E> MapAccess<'de> for TapeMapAccess<'_, 'de, I, E>
+where
+    I: Iterator<Item = usize>,
+    E: de::Error,
+{
+    type Error = E;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, E>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        match self.items.next() {
+            Some(pos) => {
+                self.remaining -= 1;
+                seed.deserialize(TapeMapKeyDeserializer(TapeRefDeserializer::new(
+                    self.tape, pos,
+                )))
+                .map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, E>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let pos = self
+            .items
+            .next()
+            .expect("MapAccess::next_value called before next_key");
+        seed.deserialize(TapeRefDeserializer::new(self.tape, pos))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+/// Also reads integer keys from strings, as formats like JSON spell them.
+struct TapeMapKeyDeserializer<'a, 'de, E>(TapeRefDeserializer<'a, 'de, E>);
+
+macro_rules! map_key_integer_method {
+    ($($name:ident, $visit:ident, $ty:ty;)*) => {
+        $(
+            fn $name<V>(self, visitor: V) -> Result<V::Value, E>
+            where
+                V: Visitor<'de>,
+            {
+                if let Some(v) = self.0.tape.str_at(self.0.pos).and_then(|s| s.parse::<$ty>().ok()) {
+                    return visitor.$visit(v);
+                }
+                self.0.deserialize_integer(visitor)
+            }
+        )*
+    };
+}
+
+impl<'de, E> Deserializer<'de> for TapeMapKeyDeserializer<'_, 'de, E>
+where
+    E: de::Error,
+{
+    type Error = E;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        self.0.deserialize_any(visitor)
+    }
+
+    map_key_integer_method! {
+        deserialize_i8, visit_i8, i8;
+        deserialize_i16, visit_i16, i16;
+        deserialize_i32, visit_i32, i32;
+        deserialize_i64, visit_i64, i64;
+        deserialize_i128, visit_i128, i128;
+        deserialize_u8, visit_u8, u8;
+        deserialize_u16, visit_u16, u16;
+        deserialize_u32, visit_u32, u32;
+        deserialize_u64, visit_u64, u64;
+        deserialize_u128, visit_u128, u128;
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &str, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0.token() {
+            Token::Newtype { .. } => {
+                visitor.visit_newtype_struct(TapeMapKeyDeserializer(self.0.inner()))
+            }
+            _ => visitor.visit_newtype_struct(self),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool f32 f64 char str string bytes byte_buf option unit unit_struct
+        seq tuple tuple_struct map struct enum identifier
+        ignored_any
+    }
+}
+
+struct TapeEnumAccess<'a, 'de, E> {
+    variant: TapeRefDeserializer<'a, 'de, E>,
+    value: Option<TapeRefDeserializer<'a, 'de, E>>,
+}
+
+impl<'a, 'de, E> EnumAccess<'de> for TapeEnumAccess<'a, 'de, E>
+where
+    E: de::Error,
+{
+    type Error = E;
+    type Variant = TapeVariantAccess<'a, 'de, E>;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant), E>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let variant = seed.deserialize(self.variant)?;
+        Ok((variant, TapeVariantAccess(self.value)))
+    }
+}
+
+struct TapeVariantAccess<'a, 'de, E>(Option<TapeRefDeserializer<'a, 'de, E>>);
+
+impl<'de, E> de::VariantAccess<'de> for TapeVariantAccess<'_, 'de, E>
+where
+    E: de::Error,
+{
+    type Error = E;
+
+    fn unit_variant(self) -> Result<(), E> {
+        match self.0 {
+            Some(value) => Deserialize::deserialize(value),
+            None => Ok(()),
+        }
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, E>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self.0 {
+            Some(value) => seed.deserialize(value),
+            None => Err(de::Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"newtype variant",
+            )),
+        }
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            Some(value) => match value.token() {
+                Token::Seq { len, .. } => value.visit_seq(len, visitor),
+                _ => Err(value.invalid_type(&"tuple variant")),
+            },
+            None => Err(de::Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"tuple variant",
+            )),
+        }
+    }
+
+    fn struct_variant<V>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            Some(value) => match value.token() {
+                Token::Map { len, .. } => value.visit_map(len, visitor),
+                Token::Seq { len, .. } => value.visit_seq(len, visitor),
+                _ => Err(value.invalid_type(&"struct variant")),
+            },
+            None => Err(de::Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"struct variant",
+            )),
+        }
+    }
+}
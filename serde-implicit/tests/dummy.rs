@@ -2,7 +2,7 @@ use serde_json::json;
 
 #[test]
 fn test_basic() {
-    #[allow(dead_code)]
+    #[allow(dead_code, clippy::enum_variant_names)]
     #[derive(serde_implicit_proc::Deserialize, Debug)]
     // #[serde(untagged)]
     enum MultiTypeTag {
@@ -24,120 +24,1272 @@ fn test_basic() {
         },
     }
 
-    let res: Result<MultiTypeTag, _> =
-        serde_json::from_value(json!({ "string_tag": "", "value": 0 }));
-    assert!(res.is_ok());
+    let res: Result<MultiTypeTag, _> =
+        serde_json::from_value(json!({ "string_tag": "", "value": 0 }));
+    assert!(res.is_ok());
+
+    let res: Result<MultiTypeTag, _> =
+        serde_json::from_value(json!({ "string_tag": "", "value": 0, "extra_field": "1234" }));
+    assert!(res.is_ok());
+
+    let res: Result<MultiTypeTag, _> =
+        serde_json::from_value(json!({ "string_tag": "", "value": "straing" }));
+
+    let err = res.unwrap_err();
+    assert!(
+        matches!(
+            &*err.to_string(),
+            r#"invalid type: string "straing", expected u32"#
+        ),
+        "{err}",
+    );
+
+    let res: Result<MultiTypeTag, _> = serde_json::from_value(json!({ "string_tag": "" }));
+
+    let err = res.unwrap_err();
+    assert!(
+        matches!(&*err.to_string(), r#"missing field `value`"#),
+        "{err}",
+    );
+
+    // output specific error message about `unique_field` (if constructor is `deny_unknown_fields`)
+    // let res: Result<MultiTypeTag, _> =
+    //     serde_json::from_value(json!({ "string_tag": "", "unique_field": "" }));
+
+    // let err = res.unwrap_err();
+    // assert!(
+    //     matches!(&*err.to_string(), r#"missing field `value`"#),
+    //     "{err}",
+    // );
+}
+
+#[test]
+fn tuple_basic() {
+    #[derive(serde_implicit::Deserialize, Debug, PartialEq)]
+    enum TupleEnum {
+        Case1(bool, u32),
+        Case2(u32),
+    }
+
+    let res: Result<TupleEnum, _> = serde_json::from_value(json!([true, 0]));
+    assert!(res.is_ok());
+
+    let res: Result<TupleEnum, _> = serde_json::from_value(json!([0]));
+
+    assert_eq!(res.unwrap(), TupleEnum::Case2(0));
+}
+
+#[test]
+fn tuple_overlap() {
+    // Because `serde-implicit` commits to the first variant which parses a tag
+    // with tuple enums, this can lead to variants being impossible to deserialize
+    // like `Case2` is here.
+    #[derive(serde_implicit::Deserialize, Debug, PartialEq)]
+    enum TupleEnum {
+        Case1(bool, u32),
+        Case2(bool, bool),
+    }
+
+    let res: Result<TupleEnum, _> = serde_json::from_value(json!([true, true]));
+    let err = res.unwrap_err();
+    assert!(
+        matches!(
+            &*err.to_string(),
+            r#"TupleEnum::Case1: invalid type: boolean `true`, expected u32"#
+        ),
+        "{err}",
+    );
+}
+
+#[test]
+fn fallthrough_basic() {
+    #[allow(dead_code)]
+    #[derive(serde_implicit_proc::Deserialize, Debug)]
+    enum EnumWithFallThrough<T> {
+        Multiple {
+            #[serde_implicit(tag)]
+            variants: Vec<u32>,
+        },
+        #[serde_implicit(fallthrough)]
+        Single { one: T },
+    }
+
+    // #[derive(serde::Deserialize)]
+    // struct Other {
+    //     field: u32,
+    // }
+
+    let res: Result<EnumWithFallThrough<u32>, _> = serde_json::from_value(json!(42));
+    res.unwrap();
+
+    let res: Result<EnumWithFallThrough<u32>, _> = serde_json::from_value(json!(42.5));
+    let err = res.unwrap_err();
+
+    assert!(
+        matches!(
+            &*err.to_string(),
+            r#"invalid type: floating point `42.5`, expected u32"#
+        ),
+        "{err}",
+    );
+
+    let res: Result<EnumWithFallThrough<u32>, _> =
+        serde_json::from_value(json!({"variants": [32]}));
+    res.unwrap();
+}
+
+#[test]
+fn fallthrough_any_shape() {
+    #[derive(serde_implicit_proc::Deserialize, Debug, PartialEq)]
+    enum Catch {
+        Known {
+            #[serde_implicit(tag)]
+            known: u32,
+        },
+        #[serde_implicit(fallthrough)]
+        Raw { value: serde_json::Value },
+    }
+
+    for value in [
+        json!(true),
+        json!(42.5),
+        json!(null),
+        json!("text"),
+        json!([1, "two", [3]]),
+        json!({ "other": 1 }),
+    ] {
+        let res: Catch = serde_json::from_value(value.clone()).unwrap();
+        assert_eq!(res, Catch::Raw { value });
+    }
+
+    let res: Catch = serde_json::from_value(json!({ "known": 1 })).unwrap();
+    assert_eq!(res, Catch::Known { known: 1 });
+
+    #[derive(serde_implicit_proc::Deserialize, Debug)]
+    #[allow(dead_code)]
+    enum NoCatch {
+        Known {
+            #[serde_implicit(tag)]
+            known: u32,
+        },
+    }
+
+    let err = serde_json::from_value::<NoCatch>(json!([1])).unwrap_err();
+    assert_eq!(err.to_string(), "invalid type: sequence, expected NoCatch");
+}
+
+#[test]
+fn fallthrough_newtype() {
+    #[derive(serde_implicit_proc::Deserialize, Debug, PartialEq)]
+    enum Event {
+        Click {
+            #[serde_implicit(tag)]
+            button: u8,
+        },
+        #[serde_implicit(fallthrough)]
+        Unknown(serde_json::Value),
+    }
+
+    let res: Event = serde_json::from_value(json!({ "button": 1 })).unwrap();
+    assert_eq!(res, Event::Click { button: 1 });
+
+    let res: Event = serde_json::from_value(json!({ "key": "a" })).unwrap();
+    assert_eq!(res, Event::Unknown(json!({ "key": "a" })));
+
+    let res: Event = serde_json::from_value(json!([1, 2])).unwrap();
+    assert_eq!(res, Event::Unknown(json!([1, 2])));
+}
+
+#[test]
+fn fallthrough_keys() {
+    #[derive(serde_implicit_proc::Deserialize, Debug, PartialEq)]
+    enum Event {
+        Click {
+            #[serde_implicit(tag)]
+            button: u8,
+        },
+        #[serde_implicit(fallthrough)]
+        Unknown {
+            #[serde_implicit(keys)]
+            keys: Vec<String>,
+            value: serde_json::Value,
+        },
+    }
+
+    let res: Event = serde_json::from_value(json!({ "poll": 1, "question": "?" })).unwrap();
+    assert_eq!(
+        res,
+        Event::Unknown {
+            keys: vec!["poll".into(), "question".into()],
+            value: json!({ "poll": 1, "question": "?" }),
+        }
+    );
+
+    let res: Event = serde_json::from_value(json!("poll")).unwrap();
+    assert_eq!(
+        res,
+        Event::Unknown {
+            keys: vec![],
+            value: json!("poll"),
+        }
+    );
+
+    let res: Event = serde_json::from_value(json!({ "button": 2 })).unwrap();
+    assert_eq!(res, Event::Click { button: 2 });
+}
+
+#[test]
+fn fallthrough_multiple() {
+    #[derive(serde::Deserialize, Debug, PartialEq)]
+    #[serde(deny_unknown_fields)]
+    struct LegacyA {
+        id: u32,
+    }
+
+    #[derive(serde::Deserialize, Debug, PartialEq)]
+    #[serde(deny_unknown_fields)]
+    struct LegacyB {
+        name: String,
+    }
+
+    #[derive(serde_implicit_proc::Deserialize, Debug, PartialEq)]
+    enum Message {
+        Current {
+            #[serde_implicit(tag)]
+            version: u32,
+        },
+        #[serde_implicit(fallthrough)]
+        A(LegacyA),
+        #[serde_implicit(fallthrough)]
+        B(LegacyB),
+        #[serde_implicit(fallthrough)]
+        Raw {
+            #[serde_implicit(keys)]
+            keys: Vec<String>,
+            value: serde_json::Value,
+        },
+    }
+
+    let res: Message = serde_json::from_value(json!({ "version": 2 })).unwrap();
+    assert_eq!(res, Message::Current { version: 2 });
+
+    let res: Message = serde_json::from_value(json!({ "id": 1 })).unwrap();
+    assert_eq!(res, Message::A(LegacyA { id: 1 }));
+
+    let res: Message = serde_json::from_value(json!({ "name": "b" })).unwrap();
+    assert_eq!(res, Message::B(LegacyB { name: "b".into() }));
+
+    let res: Message = serde_json::from_value(json!({ "other": true })).unwrap();
+    assert_eq!(
+        res,
+        Message::Raw {
+            keys: vec!["other".into()],
+            value: json!({ "other": true }),
+        }
+    );
+
+    #[derive(serde_implicit_proc::Deserialize, Debug)]
+    #[allow(dead_code)]
+    enum Strict {
+        Current {
+            #[serde_implicit(tag)]
+            version: u32,
+        },
+        #[serde_implicit(fallthrough)]
+        A(LegacyA),
+        #[serde_implicit(fallthrough)]
+        B(LegacyB),
+    }
+
+    let err = serde_json::from_value::<Strict>(json!({ "id": "x" })).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "data did not match any variant of enum Strict: \
+         Strict::A: invalid type: string \"x\", expected u32; \
+         Strict::B: unknown field `id`, expected `name`"
+    );
+}
+
+#[test]
+fn allow_null_tag() {
+    #[derive(serde_implicit_proc::Deserialize, Debug, PartialEq)]
+    enum Record {
+        Live {
+            #[serde_implicit(tag)]
+            body: String,
+            deleted_at: Option<u64>,
+        },
+        Tombstone {
+            #[serde_implicit(tag, allow_null)]
+            deleted_at: Option<u64>,
+        },
+    }
+
+    let res: Record = serde_json::from_value(json!({ "deleted_at": null })).unwrap();
+    assert_eq!(res, Record::Tombstone { deleted_at: None });
+
+    let res: Record = serde_json::from_value(json!({ "deleted_at": 5 })).unwrap();
+    assert_eq!(
+        res,
+        Record::Tombstone {
+            deleted_at: Some(5)
+        }
+    );
+
+    let res: Record = serde_json::from_value(json!({ "body": "hi" })).unwrap();
+    assert_eq!(
+        res,
+        Record::Live {
+            body: "hi".into(),
+            deleted_at: None
+        }
+    );
+
+    // A null tag gives way to a non-null one
+    let res: Record = serde_json::from_value(json!({ "body": "hi", "deleted_at": null })).unwrap();
+    assert_eq!(
+        res,
+        Record::Live {
+            body: "hi".into(),
+            deleted_at: None
+        }
+    );
+
+    // Without `allow_null` a null tag is still skipped
+    #[derive(serde_implicit_proc::Deserialize, Debug)]
+    #[allow(dead_code)]
+    enum Skipped {
+        Tombstone {
+            #[serde_implicit(tag)]
+            deleted_at: u64,
+        },
+    }
+
+    let res = serde_json::from_value::<Skipped>(json!({ "deleted_at": null }));
+    assert!(res.is_err(), "{res:?}");
+}
+
+#[test]
+fn tag_priority() {
+    #[derive(serde_implicit_proc::Deserialize, Debug, PartialEq)]
+    enum Post {
+        Text {
+            #[serde_implicit(tag)]
+            content: String,
+        },
+        Image {
+            #[serde_implicit(tag, priority = 1)]
+            image_url: String,
+        },
+        Draft {
+            #[serde_implicit(tag, priority = -1)]
+            draft: bool,
+        },
+    }
+
+    let res: Post =
+        serde_json::from_value(json!({ "content": "hi", "image_url": "a.png" })).unwrap();
+    assert_eq!(
+        res,
+        Post::Image {
+            image_url: "a.png".into()
+        }
+    );
+
+    let res: Post = serde_json::from_value(json!({ "draft": true, "content": "hi" })).unwrap();
+    assert_eq!(
+        res,
+        Post::Text {
+            content: "hi".into()
+        }
+    );
+
+    // Equal priorities keep the strict error
+    #[derive(serde_implicit_proc::Deserialize, Debug)]
+    #[allow(dead_code)]
+    enum Strict {
+        Text {
+            #[serde_implicit(tag)]
+            content: String,
+        },
+        Image {
+            #[serde_implicit(tag)]
+            image_url: String,
+        },
+    }
+
+    let err = serde_json::from_value::<Strict>(json!({ "content": "hi", "image_url": "a.png" }))
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "found multiple implicit tag fields: `content` and `image_url`"
+    );
+
+    // A tie is only an error if no tag outranks it, wherever it comes
+    #[derive(serde_implicit_proc::Deserialize, Debug, PartialEq)]
+    enum Ranked {
+        A {
+            #[serde_implicit(tag, priority = 1)]
+            a: u8,
+        },
+        B {
+            #[serde_implicit(tag, priority = 1)]
+            b: u8,
+        },
+        C {
+            #[serde_implicit(tag, priority = 5)]
+            c: u8,
+        },
+    }
+
+    for input in [
+        r#"{"a":1,"b":1,"c":1}"#,
+        r#"{"c":1,"a":1,"b":1}"#,
+        r#"{"a":1,"c":1,"b":1}"#,
+    ] {
+        let res: Ranked = serde_json::from_str(input).unwrap();
+        assert_eq!(res, Ranked::C { c: 1 }, "{input}");
+    }
+
+    let err = serde_json::from_str::<Ranked>(r#"{"a":1,"b":1}"#).unwrap_err();
+    assert!(
+        err.to_string()
+            .starts_with("found multiple implicit tag fields: `a` and `b`"),
+        "{err}"
+    );
+}
+
+#[test]
+fn match_keys() {
+    #[derive(serde_implicit_proc::Deserialize, Debug, PartialEq)]
+    #[serde_implicit(match_keys = "normalized")]
+    enum Webhook {
+        Image {
+            #[serde_implicit(tag)]
+            image_url: String,
+            alt_text: Option<String>,
+        },
+        Text {
+            #[serde_implicit(tag)]
+            content: String,
+        },
+    }
+
+    for key in ["image_url", "ImageURL", "imageUrl", "image-url"] {
+        let res: Webhook =
+            serde_json::from_value(json!({ key: "a.png", "AltText": "alt" })).unwrap();
+        assert_eq!(
+            res,
+            Webhook::Image {
+                image_url: "a.png".into(),
+                alt_text: Some("alt".into()),
+            },
+            "{key}"
+        );
+    }
+
+    #[derive(serde_implicit_proc::Deserialize, Debug, PartialEq)]
+    #[serde_implicit(match_keys = "case_insensitive")]
+    enum Cased {
+        Image {
+            #[serde_implicit(tag)]
+            image_url: String,
+        },
+    }
+
+    let res: Cased = serde_json::from_value(json!({ "IMAGE_URL": "a.png" })).unwrap();
+    assert_eq!(
+        res,
+        Cased::Image {
+            image_url: "a.png".into()
+        }
+    );
+
+    let res = serde_json::from_value::<Cased>(json!({ "imageUrl": "a.png" }));
+    assert!(res.is_err(), "{res:?}");
+}
+
+#[test]
+fn bytes_keys() {
+    use serde::Deserialize;
+    use serde::de::value::{Error, MapDeserializer};
+
+    #[derive(serde_implicit_proc::Deserialize, Debug, PartialEq)]
+    enum Exact {
+        Pair {
+            #[serde_implicit(tag)]
+            left: u32,
+            right: u32,
+        },
+    }
+
+    #[derive(serde_implicit_proc::Deserialize, Debug, PartialEq)]
+    #[serde_implicit(match_keys = "case_insensitive")]
+    enum Folded {
+        Pair {
+            #[serde_implicit(tag)]
+            left: u32,
+            right: u32,
+        },
+    }
+
+    let input =
+        || MapDeserializer::<_, Error>::new(vec![(&b"left"[..], 1u32), (b"right", 2)].into_iter());
+    assert_eq!(
+        Exact::deserialize(input()).unwrap(),
+        Exact::Pair { left: 1, right: 2 }
+    );
+
+    let input =
+        MapDeserializer::<_, Error>::new(vec![(&b"LEFT"[..], 1u32), (b"Right", 2)].into_iter());
+    assert_eq!(
+        Folded::deserialize(input).unwrap(),
+        Folded::Pair { left: 1, right: 2 }
+    );
+}
+
+#[test]
+fn integer_keys() {
+    use serde::Deserialize;
+    use serde::de::value::MapDeserializer;
+    use serde_json::Error;
+    use std::collections::BTreeMap;
+
+    #[derive(serde_implicit_proc::Deserialize, Debug, PartialEq)]
+    enum Compact {
+        Login {
+            #[serde_implicit(tag, key = 1)]
+            user: String,
+            #[serde_implicit(key = 2)]
+            token: u64,
+        },
+        Logout {
+            #[serde_implicit(tag, key = 3)]
+            session: u64,
+        },
+    }
+
+    let input =
+        MapDeserializer::<_, Error>::new(vec![(3u64, serde_json::Value::from(7))].into_iter());
+    assert_eq!(
+        Compact::deserialize(input).unwrap(),
+        Compact::Logout { session: 7 }
+    );
+
+    // Small keys as produced by compact formats, and unknown integer keys are ignored
+    let input = MapDeserializer::<_, Error>::new(
+        vec![
+            (2u8, serde_json::Value::from(9)),
+            (1, serde_json::Value::from("me")),
+            (42, serde_json::Value::from(true)),
+        ]
+        .into_iter(),
+    );
+    assert_eq!(
+        Compact::deserialize(input).unwrap(),
+        Compact::Login {
+            user: "me".into(),
+            token: 9
+        }
+    );
+
+    // Names still match
+    let res: Compact = serde_json::from_value(json!({ "user": "me", "token": 9 })).unwrap();
+    assert_eq!(
+        res,
+        Compact::Login {
+            user: "me".into(),
+            token: 9
+        }
+    );
+
+    let input: BTreeMap<u64, serde_json::Value> =
+        [(1, json!("me")), (3, json!(1))].into_iter().collect();
+    let err =
+        Compact::deserialize(MapDeserializer::<_, Error>::new(input.into_iter())).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "found multiple implicit tag fields: `1` and `3`"
+    );
+}
+
+#[test]
+fn accept_seq() {
+    use serde::Deserialize;
+    use serde_implicit::{FromContentRef, content::Content};
+
+    #[derive(serde_implicit_proc::Deserialize, Debug, PartialEq)]
+    #[serde_implicit(accept_seq)]
+    enum Reading {
+        Temperature {
+            #[serde_implicit(tag)]
+            celsius: f32,
+            sensor: u8,
+        },
+        Humidity {
+            #[serde_implicit(tag)]
+            percent: u8,
+        },
+    }
+
+    let res: Reading = serde_json::from_value(json!([["sensor", 2], ["celsius", 21.5]])).unwrap();
+    assert_eq!(
+        res,
+        Reading::Temperature {
+            celsius: 21.5,
+            sensor: 2
+        }
+    );
+
+    let res: Reading = serde_json::from_value(json!(["celsius", 21.5, "sensor", 2])).unwrap();
+    assert_eq!(
+        res,
+        Reading::Temperature {
+            celsius: 21.5,
+            sensor: 2
+        }
+    );
+
+    let res: Reading = serde_json::from_value(json!({ "percent": 40 })).unwrap();
+    assert_eq!(res, Reading::Humidity { percent: 40 });
+
+    // The flat form must start with a tag
+    let err = serde_json::from_value::<Reading>(json!(["sensor", 2, "celsius", 21.5])).unwrap_err();
+    assert_eq!(err.to_string(), "invalid type: sequence, expected Reading");
+
+    #[derive(serde_implicit_proc::Deserialize, Debug, PartialEq)]
+    #[serde_implicit(accept_seq)]
+    enum WithRaw {
+        Humidity {
+            #[serde_implicit(tag)]
+            percent: u8,
+        },
+        #[serde_implicit(fallthrough)]
+        Raw(serde_json::Value),
+    }
+
+    let res: WithRaw = serde_json::from_value(json!([1, 2, 3])).unwrap();
+    assert_eq!(res, WithRaw::Raw(json!([1, 2, 3])));
+
+    let res: WithRaw = serde_json::from_value(json!(["percent", 3])).unwrap();
+    assert_eq!(res, WithRaw::Humidity { percent: 3 });
+
+    // Pairs without a tag reach the fallthrough as the sequence they were
+    for input in [json!([[1, 2], [3, 4]]), json!([["x", 2], ["y", 4]])] {
+        let res: WithRaw = serde_json::from_value(input.clone()).unwrap();
+        assert_eq!(res, WithRaw::Raw(input.clone()));
+
+        let content = Content::deserialize(&input).unwrap();
+        let res = WithRaw::from_content_ref::<serde_json::Error>(&content).unwrap();
+        assert_eq!(res, WithRaw::Raw(input));
+    }
+
+    let res: WithRaw = serde_json::from_value(json!([["percent", 3], ["x", 1]])).unwrap();
+    assert_eq!(res, WithRaw::Humidity { percent: 3 });
+}
+
+#[test]
+fn flatten() {
+    use std::collections::HashMap;
+
+    #[derive(serde::Deserialize, Debug, PartialEq)]
+    struct Meta {
+        id: u64,
+        source: Option<String>,
+    }
+
+    #[derive(serde_implicit_proc::Deserialize, Debug, PartialEq)]
+    enum Event {
+        Click {
+            #[serde_implicit(tag)]
+            button: u8,
+            #[serde(flatten)]
+            meta: Meta,
+            #[serde_implicit(flatten)]
+            extra: HashMap<String, serde_json::Value>,
+        },
+        Scroll {
+            #[serde_implicit(tag)]
+            delta: i32,
+        },
+    }
+
+    let res: Event = serde_json::from_value(json!({
+        "button": 1,
+        "id": 7,
+        "x": 10,
+        "y": 20,
+    }))
+    .unwrap();
+    assert_eq!(
+        res,
+        Event::Click {
+            button: 1,
+            meta: Meta {
+                id: 7,
+                source: None
+            },
+            extra: [("x".into(), json!(10)), ("y".into(), json!(20))].into(),
+        }
+    );
+
+    let err = serde_json::from_value::<Event>(json!({ "button": 1, "x": 10 })).unwrap_err();
+    assert_eq!(err.to_string(), "missing field `id`");
+}
+
+#[test]
+fn serialize_only_serde_attrs() {
+    #[derive(serde_implicit_proc::Deserialize, serde::Serialize, Debug, PartialEq)]
+    #[serde(untagged)]
+    enum Message {
+        Image {
+            #[serde_implicit(tag)]
+            image_url: String,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            caption: Option<String>,
+        },
+    }
+
+    let res: Message = serde_json::from_value(json!({ "image_url": "x" })).unwrap();
+    assert_eq!(
+        res,
+        Message::Image {
+            image_url: "x".into(),
+            caption: None
+        }
+    );
+    assert_eq!(
+        serde_json::to_value(&res).unwrap(),
+        json!({ "image_url": "x" })
+    );
+}
+
+#[test]
+fn struct_requires() {
+    #[derive(serde_implicit_proc::Deserialize, Debug, PartialEq)]
+    struct Checkout {
+        id: u64,
+        #[serde_implicit(requires = "billing_name, billing_zip")]
+        billing_address: Option<String>,
+        billing_name: Option<String>,
+        billing_zip: Option<String>,
+    }
+
+    let res: Checkout = serde_json::from_value(json!({ "id": 1 })).unwrap();
+    assert_eq!(
+        res,
+        Checkout {
+            id: 1,
+            billing_address: None,
+            billing_name: None,
+            billing_zip: None
+        }
+    );
+
+    let res: Checkout = serde_json::from_value(json!({
+        "id": 1,
+        "billing_address": "1 Main St",
+        "billing_name": "Ada",
+        "billing_zip": "12345",
+    }))
+    .unwrap();
+    assert_eq!(res.billing_name.as_deref(), Some("Ada"));
+
+    let err = serde_json::from_value::<Checkout>(json!({
+        "id": 1,
+        "billing_address": "1 Main St",
+        "billing_zip": "12345",
+    }))
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "field `billing_address` requires `billing_name`"
+    );
+
+    // `null` counts as absent on either side
+    let res: Checkout =
+        serde_json::from_value(json!({ "id": 1, "billing_address": null })).unwrap();
+    assert_eq!(res.billing_address, None);
+    let err = serde_json::from_value::<Checkout>(json!({
+        "id": 1,
+        "billing_address": "1 Main St",
+        "billing_name": "Ada",
+        "billing_zip": null,
+    }))
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "field `billing_address` requires `billing_zip`"
+    );
+
+    let err = serde_json::from_value::<Checkout>(json!({ "billing_name": "Ada" })).unwrap_err();
+    assert_eq!(err.to_string(), "missing field `id`");
+}
+
+#[test]
+fn variant_requires() {
+    #[derive(serde_implicit_proc::Deserialize, Debug, PartialEq)]
+    enum Shape {
+        Rect {
+            #[serde_implicit(tag)]
+            width: u32,
+            #[serde_implicit(requires = "height")]
+            depth: Option<u32>,
+            height: Option<u32>,
+        },
+    }
+
+    let err = serde_json::from_value::<Shape>(json!({ "width": 1, "depth": 2 })).unwrap_err();
+    assert_eq!(err.to_string(), "field `depth` requires `height`");
+}
+
+#[test]
+fn from_content_ref() {
+    use serde::Deserialize;
+    use serde_implicit::{FromContentRef, content::Content};
+    use serde_json::Error;
+
+    #[derive(serde_implicit_proc::Deserialize, Debug, PartialEq)]
+    #[serde_implicit(accept_seq)]
+    enum Event {
+        Click {
+            #[serde_implicit(tag)]
+            button: u8,
+            label: String,
+        },
+        #[serde_implicit(fallthrough)]
+        Other(serde_json::Value),
+    }
+
+    #[derive(serde_implicit_proc::Deserialize, Debug, PartialEq)]
+    struct Point {
+        x: i32,
+        y: i32,
+    }
+
+    #[derive(serde_implicit_proc::Deserialize, Debug, PartialEq)]
+    enum Pair {
+        Flag(bool),
+        Both(u32, String),
+    }
+
+    let value = json!({ "button": 1, "label": "ok" });
+    let content = Content::deserialize(&value).unwrap();
+    assert_eq!(
+        Event::from_content_ref::<Error>(&content).unwrap(),
+        Event::Click {
+            button: 1,
+            label: "ok".into()
+        }
+    );
+    // The content is still there to be read again
+    assert_eq!(
+        Event::from_content_ref::<Error>(&content).unwrap(),
+        Event::deserialize(&value).unwrap()
+    );
+
+    let value = json!([["label", "ok"], ["button", 2]]);
+    let content = Content::deserialize(&value).unwrap();
+    assert_eq!(
+        Event::from_content_ref::<Error>(&content).unwrap(),
+        Event::Click {
+            button: 2,
+            label: "ok".into()
+        }
+    );
+
+    let value = json!({ "kind": "scroll" });
+    let content = Content::deserialize(&value).unwrap();
+    assert_eq!(
+        Event::from_content_ref::<Error>(&content).unwrap(),
+        Event::Other(value.clone())
+    );
+
+    let value = json!({ "button": 1 });
+    let content = Content::deserialize(&value).unwrap();
+    let err = Event::from_content_ref::<Error>(&content).unwrap_err();
+    assert_eq!(err.to_string(), "missing field `label`");
+
+    let value = json!([7, "seven"]);
+    let content = Content::deserialize(&value).unwrap();
+    assert_eq!(
+        Pair::from_content_ref::<Error>(&content).unwrap(),
+        Pair::Both(7, "seven".into())
+    );
+
+    let value = json!({ "x": 1, "y": -1 });
+    let content = Content::deserialize(&value).unwrap();
+    assert_eq!(
+        Point::from_content_ref::<Error>(&content).unwrap(),
+        Point { x: 1, y: -1 }
+    );
+}
+
+#[test]
+fn buffer_limits() {
+    use serde_implicit::Limits;
+
+    #[derive(serde_implicit_proc::Deserialize, Debug, PartialEq)]
+    #[serde_implicit(max_depth = 2, max_len = 3)]
+    enum Small {
+        Item {
+            #[serde_implicit(tag)]
+            id: u64,
+            tags: Vec<String>,
+        },
+    }
+
+    #[derive(serde_implicit_proc::Deserialize, Debug, PartialEq)]
+    enum Open {
+        Item {
+            #[serde_implicit(tag)]
+            id: u64,
+            data: serde_json::Value,
+        },
+    }
+
+    #[derive(serde_implicit_proc::Deserialize, Debug, PartialEq)]
+    enum Pair {
+        Both(u32, Vec<u32>),
+    }
+
+    #[derive(serde_implicit_proc::Deserialize, Debug, PartialEq)]
+    enum Caught {
+        Item {
+            #[serde_implicit(tag)]
+            id: u64,
+        },
+        #[serde_implicit(fallthrough)]
+        Other(serde_json::Value),
+    }
+
+    let res: Small = serde_json::from_str(r#"{"id":1,"tags":["a","b"]}"#).unwrap();
+    assert_eq!(
+        res,
+        Small::Item {
+            id: 1,
+            tags: vec!["a".into(), "b".into()]
+        }
+    );
+
+    let err = serde_json::from_str::<Small>(r#"{"id":1,"tags":[["a"]]}"#).unwrap_err();
+    assert!(
+        err.to_string()
+            .starts_with("input nested deeper than the limit of 2"),
+        "{err}"
+    );
+
+    let err = serde_json::from_str::<Small>(r#"{"id":1,"tags":[],"x":1,"y":2}"#).unwrap_err();
+    assert!(
+        err.to_string()
+            .starts_with("sequence or map longer than the limit of 3 elements"),
+        "{err}"
+    );
+
+    // The thread's default applies to types without their own limits. Only
+    // strings copied out of the input count, so read from a reader
+    let text = "x".repeat(300);
+    let long = json!({ "id": 1, "data": text }).to_string();
+    Limits::set_thread_default(Limits {
+        max_bytes: Some(256),
+        ..Limits::NONE
+    });
+    let err = serde_json::from_reader::<_, Open>(long.as_bytes()).unwrap_err();
+    assert!(
+        err.to_string()
+            .starts_with("input larger than the limit of 256 buffered bytes"),
+        "{err}"
+    );
+    // A string caught as a whole by the fallthrough counts too
+    let bare = json!(text).to_string();
+    let err = serde_json::from_reader::<_, Caught>(bare.as_bytes()).unwrap_err();
+    assert!(
+        err.to_string()
+            .starts_with("input larger than the limit of 256 buffered bytes"),
+        "{err}"
+    );
+    let many = json!([1, vec![2; 100]]).to_string();
+    let err = serde_json::from_reader::<_, Pair>(many.as_bytes()).unwrap_err();
+    assert!(
+        err.to_string()
+            .starts_with("input larger than the limit of 256 buffered bytes"),
+        "{err}"
+    );
+
+    // Enum attributes override only the limits they set
+    let tagged = json!({ "id": 1, "tags": [text] }).to_string();
+    let err = serde_json::from_reader::<_, Small>(tagged.as_bytes()).unwrap_err();
+    assert!(
+        err.to_string()
+            .starts_with("input larger than the limit of 256 buffered bytes"),
+        "{err}"
+    );
 
-    let res: Result<MultiTypeTag, _> =
-        serde_json::from_value(json!({ "string_tag": "", "value": 0, "extra_field": "1234" }));
-    assert!(res.is_ok());
+    Limits::set_thread_default(Limits::NONE);
+    assert!(serde_json::from_reader::<_, Open>(long.as_bytes()).is_ok());
+    assert!(serde_json::from_reader::<_, Caught>(bare.as_bytes()).is_ok());
+    assert!(serde_json::from_reader::<_, Pair>(many.as_bytes()).is_ok());
+}
 
-    let res: Result<MultiTypeTag, _> =
-        serde_json::from_value(json!({ "string_tag": "", "value": "straing" }));
+#[test]
+fn content_api() {
+    use serde::Deserialize;
+    use serde_implicit::content::Content;
+
+    let value = json!({ "id": 7, "name": "ada", "tags": ["a", "b"], "score": -1.5, "none": null });
+    let content = Content::deserialize(&value).unwrap();
+
+    assert_eq!(content.get("id").and_then(Content::as_u64), Some(7));
+    assert_eq!(content.get("id").and_then(Content::as_i64), Some(7));
+    assert_eq!(content.get("id").and_then(Content::as_f64), Some(7.0));
+    assert_eq!(content.get("score").and_then(Content::as_f64), Some(-1.5));
+    assert_eq!(content.get("score").and_then(Content::as_u64), None);
+    assert_eq!(content.get("name").and_then(Content::as_str), Some("ada"));
+    assert!(content.get("none").is_some_and(Content::is_null));
+    assert_eq!(content.get("missing"), None);
+    assert_eq!(
+        content
+            .get("tags")
+            .and_then(Content::as_seq)
+            .map(<[_]>::len),
+        Some(2)
+    );
+    assert_eq!(content.as_map().map(<[_]>::len), Some(5));
+    assert_eq!(Content::I8(-1).as_u64(), None);
+    assert_eq!(Content::U64(u64::MAX).as_i64(), None);
 
-    let err = res.unwrap_err();
-    assert!(
-        matches!(
-            &*err.to_string(),
-            r#"invalid type: string "straing", expected u32"#
-        ),
-        "{err}",
+    // Borrowed and owned strings compare equal, integer widths do not
+    assert_eq!(content.get("name"), Some(&Content::String("ada".into())));
+    assert_ne!(Content::U8(1), Content::U64(1));
+    assert_eq!(
+        Content::from(Some(1u8)),
+        Content::Some(Box::new(Content::U8(1)))
     );
 
-    let res: Result<MultiTypeTag, _> = serde_json::from_value(json!({ "string_tag": "" }));
+    let built = Content::Map(vec![
+        ("id".into(), 7u64.into()),
+        ("name".into(), "ada".into()),
+        ("none".into(), Content::Unit),
+        ("score".into(), (-1.5f64).into()),
+        ("tags".into(), ["a", "b"].into_iter().collect()),
+    ]);
+    assert_eq!(built, content);
+    assert_eq!(serde_json::to_value(&built).unwrap(), value);
+
+    let owned: Content<'static> = {
+        let value = value.clone();
+        Content::deserialize(&value).unwrap().into_owned()
+    };
+    assert_eq!(owned, content);
+}
 
-    let err = res.unwrap_err();
-    assert!(
-        matches!(&*err.to_string(), r#"missing field `value`"#),
-        "{err}",
+#[test]
+fn content_serialize_json() {
+    use serde::Deserialize;
+    use serde_implicit::content::Content;
+
+    let input = r#"{"id":7,"name":"ada","tags":["a",null,{"x":-1.5}],"flag":true}"#;
+    let mut de = serde_json::Deserializer::from_str(input);
+    let content = Content::deserialize(&mut de).unwrap();
+    assert_eq!(serde_json::to_string(&content).unwrap(), input);
+
+    // Shapes JSON cannot spell are still emitted the way serde would
+    let content = Content::Seq(vec![
+        Content::Some(Box::new(Content::U8(1))),
+        Content::None,
+        Content::Unit,
+        Content::Newtype(Box::new("inner".into())),
+        Content::Char('c'),
+        Content::Bytes(&[1, 2]),
+    ]);
+    assert_eq!(
+        serde_json::to_value(&content).unwrap(),
+        json!([1, null, null, "inner", "c", [1, 2]])
     );
+}
 
-    // output specific error message about `unique_field` (if constructor is `deny_unknown_fields`)
-    // let res: Result<MultiTypeTag, _> =
-    //     serde_json::from_value(json!({ "string_tag": "", "unique_field": "" }));
+#[test]
+fn content_serialize_msgpack() {
+    use serde::{Deserialize, Serialize};
+    use serde_implicit::content::Content;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Meters(f64);
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Sample {
+        id: u64,
+        label: Option<String>,
+        missing: Option<i32>,
+        distance: Meters,
+        unit: (),
+        delta: i8,
+        pairs: Vec<(u8, char)>,
+    }
 
-    // let err = res.unwrap_err();
-    // assert!(
-    //     matches!(&*err.to_string(), r#"missing field `value`"#),
-    //     "{err}",
-    // );
+    let sample = Sample {
+        id: 1 << 40,
+        label: Some("ada".into()),
+        missing: None,
+        distance: Meters(2.5),
+        unit: (),
+        delta: -3,
+        pairs: vec![(1, 'a'), (2, 'b')],
+    };
+
+    // Re-emitting the buffered content reproduces the original encoding
+    let bytes = rmp_serde::to_vec_named(&sample).unwrap();
+    let content: Content = rmp_serde::from_slice(&bytes).unwrap();
+    let reencoded = rmp_serde::to_vec_named(&content).unwrap();
+    assert_eq!(reencoded, bytes);
+    assert_eq!(rmp_serde::from_slice::<Sample>(&reencoded).unwrap(), sample);
+
+    // Binary data stays binary instead of becoming a sequence
+    let content = Content::Map(vec![
+        ("blob".into(), Content::Bytes(&[0, 159, 255])),
+        (1u8.into(), Content::ByteBuf(vec![7])),
+    ]);
+    let bytes = rmp_serde::to_vec(&content).unwrap();
+    let decoded: Content = rmp_serde::from_slice(&bytes).unwrap();
+    assert_eq!(decoded, content);
 }
 
 #[test]
-fn tuple_basic() {
-    #[derive(serde_implicit::Deserialize, Debug, PartialEq)]
-    enum TupleEnum {
-        Case1(bool, u32),
-        Case2(u32),
-    }
+fn fallthrough_content() {
+    use serde_implicit::content::Content;
 
-    let res: Result<TupleEnum, _> = serde_json::from_value(json!([true, 0]));
-    assert!(res.is_ok());
+    #[derive(serde_implicit_proc::Deserialize, Debug, PartialEq)]
+    enum Event<'a> {
+        Click {
+            #[serde_implicit(tag)]
+            button: u8,
+            label: &'a str,
+        },
+        #[serde_implicit(fallthrough)]
+        Other(#[serde(borrow)] Content<'a>),
+    }
 
-    let res: Result<TupleEnum, _> = serde_json::from_value(json!([0]));
+    let input = r#"{"kind":"scroll","delta":[-1,2.5],"meta":null}"#;
+    let Event::Other(content) = serde_json::from_str(input).unwrap() else {
+        panic!("expected the fallthrough variant")
+    };
+    assert_eq!(serde_json::to_string(&content).unwrap(), input);
 
-    assert_eq!(res.unwrap(), TupleEnum::Case2(0));
+    let res: Event = serde_json::from_str(r#"{"button":1,"label":"ok"}"#).unwrap();
+    assert_eq!(
+        res,
+        Event::Click {
+            button: 1,
+            label: "ok"
+        }
+    );
 }
 
 #[test]
-fn tuple_overlap() {
-    // Because `serde-implicit` commits to the first variant which parses a tag
-    // with tuple enums, this can lead to variants being impossible to deserialize
-    // like `Case2` is here.
-    #[derive(serde_implicit::Deserialize, Debug, PartialEq)]
-    enum TupleEnum {
-        Case1(bool, u32),
-        Case2(bool, bool),
+fn borrowed_fields() {
+    use serde::Deserialize;
+    use std::borrow::Cow;
+
+    #[derive(serde_implicit_proc::Deserialize, Debug, PartialEq)]
+    enum Record<'a, 'b> {
+        Named {
+            #[serde_implicit(tag)]
+            name: &'a str,
+            #[serde(borrow)]
+            alias: Cow<'b, str>,
+            note: Cow<'b, str>,
+        },
     }
 
-    let res: Result<TupleEnum, _> = serde_json::from_value(json!([true, true]));
-    let err = res.unwrap_err();
-    assert!(
-        matches!(
-            &*err.to_string(),
-            r#"TupleEnum::Case1: invalid type: boolean `true`, expected u32"#
-        ),
-        "{err}",
+    #[derive(serde_implicit_proc::Deserialize, Debug, PartialEq)]
+    struct Plain<'a> {
+        id: &'a str,
+    }
+
+    // `'de` outlives both lifetimes, `'b` because of `#[serde(borrow)]`
+    let input = r#"{"name":"ada","alias":"countess","note":"analyst"}"#;
+    let Record::Named { name, alias, note } = serde_json::from_str(input).unwrap();
+    assert_eq!(name, "ada");
+    assert_eq!(alias, "countess");
+    assert_eq!(note, "analyst");
+
+    assert_eq!(
+        serde_json::from_str::<Plain>(r#"{"id":"x1"}"#).unwrap(),
+        Plain { id: "x1" }
+    );
+
+    // A `Cow` without `borrow` is not bound to the input and reads owned data
+    #[derive(serde_implicit_proc::Deserialize, Debug, PartialEq)]
+    enum Label<'a> {
+        Text {
+            #[serde_implicit(tag)]
+            text: Cow<'a, str>,
+        },
+    }
+    let value = json!({ "text": "owned" });
+    assert_eq!(
+        Label::deserialize(value).unwrap(),
+        Label::Text {
+            text: "owned".into()
+        }
     );
 }
 
+#[cfg(feature = "serde_json")]
 #[test]
-fn fallthrough_basic() {
-    #[allow(dead_code)]
-    #[derive(serde_implicit_proc::Deserialize, Debug)]
-    enum EnumWithFallThrough<T> {
-        Multiple {
+fn from_value_ref() {
+    #[derive(serde_implicit_proc::Deserialize, Debug, PartialEq)]
+    enum Event {
+        Click {
             #[serde_implicit(tag)]
-            variants: Vec<u32>,
-        },
-        Single {
-            one: T,
+            button: u8,
+            label: String,
         },
     }
 
-    // #[derive(serde::Deserialize)]
-    // struct Other {
-    //     field: u32,
-    // }
-
-    let res: Result<EnumWithFallThrough<u32>, _> = serde_json::from_value(json!(42));
-    res.unwrap();
-
-    let res: Result<EnumWithFallThrough<u32>, _> = serde_json::from_value(json!(42.5));
-    let err = res.unwrap_err();
+    let value = json!({ "button": 1, "label": "ok" });
+    let res: Event = serde_implicit::from_value_ref(&value).unwrap();
+    assert_eq!(
+        res,
+        Event::Click {
+            button: 1,
+            label: "ok".into()
+        }
+    );
+}
 
-    assert!(
-        matches!(
-            &*err.to_string(),
-            // todo provide more specific diagnostics
-            r#"invalid type: floating point `42.5`, expected EnumWithFallThrough"#
+#[cfg(feature = "serde_json")]
+#[test]
+fn content_json_conversions() {
+    use serde::Deserialize;
+    use serde_implicit::content::Content;
+
+    let value = json!({ "id": 7, "neg": -2, "score": 1.5, "name": "ada", "tags": [true, null] });
+
+    let borrowed = Content::from(&value);
+    assert!(matches!(borrowed.get("name"), Some(Content::Str("ada"))));
+    assert_eq!(borrowed, Content::deserialize(&value).unwrap());
+
+    let owned: Content<'static> = value.clone().into();
+    assert_eq!(owned, borrowed);
+    assert_eq!(serde_json::Value::from(owned), value);
+
+    // Shapes JSON has no spelling for convert like `serde_json::to_value`
+    let content = Content::Map(vec![
+        (Content::U8(1), Content::Some(Box::new(Content::Char('c')))),
+        ("bytes".into(), Content::Bytes(&[1, 2])),
+        ("nan".into(), Content::F64(f64::NAN)),
+        (
+            Content::Bool(true),
+            Content::Newtype(Box::new(Content::Unit)),
         ),
-        "{err}",
+        ("small".into(), Content::U128(7)),
+        ("wide".into(), Content::I128(-1 << 64)),
+    ]);
+    assert_eq!(
+        serde_json::Value::from(content),
+        json!({ "1": "c", "bytes": [1, 2], "nan": null, "true": null, "small": 7, "wide": -(2f64.powi(64)) })
     );
-
-    let res: Result<EnumWithFallThrough<u32>, _> =
-        serde_json::from_value(json!({"variants": [32]}));
-    res.unwrap();
 }
 
 #[test]
@@ -217,6 +1369,7 @@ fn tuple_mixed_tag_positions() {
 
 #[test]
 fn tuple_custom_tag_no_match() {
+    #[allow(dead_code)]
     #[derive(serde_implicit::Deserialize, Debug)]
     enum TupleEnum {
         Case1(#[serde_implicit(tag)] String, u32),
@@ -376,6 +1529,213 @@ fn tuple_flatten_no_match() {
     assert!(err.to_string().contains("data did not match any variant"));
 }
 
+#[test]
+fn tuple_no_match_reports_each_variant() {
+    #[derive(serde::Deserialize, Debug, PartialEq)]
+    struct OnlyBools(bool, bool);
+
+    #[allow(dead_code)]
+    #[derive(serde_implicit::Deserialize, Debug)]
+    enum TestEnum {
+        Single(u64),
+        Pair(#[serde_implicit(tag)] String, u32),
+        Bools(#[serde_implicit(flatten)] OnlyBools),
+    }
+
+    let err = serde_json::from_value::<TestEnum>(json!([1, 2])).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "data did not match any variant of enum TestEnum: \
+         TestEnum::Single: expected a sequence of 1 element(s), found 2; \
+         TestEnum::Pair: tag at index 0: invalid type: integer `1`, expected a string; \
+         TestEnum::Bools: invalid type: integer `1`, expected a boolean",
+    );
+
+    let err = serde_json::from_value::<TestEnum>(json!({ "a": 1 })).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "data did not match any variant of enum TestEnum: \
+         TestEnum::Single: invalid type: map, expected u64; \
+         TestEnum::Pair: expected a sequence of 2 element(s), found map; \
+         TestEnum::Bools: invalid type: map, expected tuple struct OnlyBools",
+    );
+}
+
+#[test]
+fn tuple_nested_tag_path() {
+    #[derive(serde::Deserialize, Debug, PartialEq)]
+    enum Kind {
+        Create,
+    }
+
+    #[derive(serde::Deserialize, Debug, PartialEq)]
+    enum Other {
+        Delete,
+    }
+
+    #[derive(serde_implicit::Deserialize, Debug, PartialEq)]
+    enum Event {
+        // `[[kind, version], payload]`, discriminated by `kind`
+        Create(
+            #[serde_implicit(tag, path = "0", tag_type = "Kind")] (Kind, u32),
+            String,
+        ),
+        Delete(
+            #[serde_implicit(tag, path = "0", tag_type = "Other")] (Other, u32),
+            u64,
+        ),
+        Wrapped(#[serde_implicit(tag, path = "1.0", tag_type = "bool")] (u32, (bool, u32))),
+    }
+
+    let res: Result<Event, _> = serde_json::from_value(json!([["Create", 1], "hi"]));
+    assert_eq!(res.unwrap(), Event::Create((Kind::Create, 1), "hi".into()));
+
+    let res: Result<Event, _> = serde_json::from_value(json!([["Delete", 2], 7]));
+    assert_eq!(res.unwrap(), Event::Delete((Other::Delete, 2), 7));
+
+    let res: Result<Event, _> = serde_json::from_value(json!([[3, [true, 4]]]));
+    assert_eq!(res.unwrap(), Event::Wrapped((3, (true, 4))));
+
+    // The nested tag commits to `Create` even though the version is invalid
+    let err = serde_json::from_value::<Event>(json!([["Create", "one"], "hi"])).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        r#"Event::Create: invalid type: string "one", expected u32"#
+    );
+
+    let err = serde_json::from_value::<Event>(json!([[], 7])).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "data did not match any variant of enum Event: \
+         Event::Create: no value at tag index 0.0; \
+         Event::Delete: no value at tag index 0.0; \
+         Event::Wrapped: expected a sequence of 1 element(s), found 2",
+    );
+}
+
+#[test]
+fn tuple_tag_with_predicate() {
+    use serde_implicit::content::Content;
+
+    fn is_urn(c: &Content) -> bool {
+        c.as_str().is_some_and(|s| s.starts_with("urn:"))
+    }
+
+    fn is_small(c: &Content) -> bool {
+        matches!(c, Content::U64(n) if *n < 10)
+    }
+
+    #[derive(serde_implicit::Deserialize, Debug, PartialEq)]
+    enum Reference {
+        Urn(#[serde_implicit(tag_with = "is_urn")] String),
+        Small(#[serde_implicit(tag_with = "is_small")] u8, String),
+        Large(u64, String),
+        Url(String),
+    }
+
+    let res: Result<Reference, _> = serde_json::from_value(json!("urn:isbn:123"));
+    assert_eq!(res.unwrap(), Reference::Urn("urn:isbn:123".into()));
+
+    let res: Result<Reference, _> = serde_json::from_value(json!("https://example.com"));
+    assert_eq!(res.unwrap(), Reference::Url("https://example.com".into()));
+
+    let res: Result<Reference, _> = serde_json::from_value(json!([3, "three"]));
+    assert_eq!(res.unwrap(), Reference::Small(3, "three".into()));
+
+    let res: Result<Reference, _> = serde_json::from_value(json!([300, "many"]));
+    assert_eq!(res.unwrap(), Reference::Large(300, "many".into()));
+
+    let err = serde_json::from_value::<Reference>(json!([true, "many"])).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "data did not match any variant of enum Reference: \
+         Reference::Urn: expected a sequence of 1 element(s), found 2; \
+         Reference::Small: tag at index 0 rejected by `is_small`; \
+         Reference::Large: tag at index 0: invalid type: boolean `true`, expected u64; \
+         Reference::Url: expected a sequence of 1 element(s), found 2",
+    );
+}
+
+#[test]
+fn tuple_generics() {
+    use std::borrow::Cow;
+    use std::fmt::Debug;
+
+    #[derive(serde::Deserialize, Debug, PartialEq)]
+    enum Op {
+        Sum,
+    }
+
+    #[derive(serde_implicit::Deserialize, Debug, PartialEq)]
+    enum Expr<T> {
+        Lit(T),
+        Op(Op, Vec<Expr<T>>),
+    }
+
+    let res: Result<Expr<u32>, _> = serde_json::from_value(json!(["Sum", [1, ["Sum", [2]]]]));
+    assert_eq!(
+        res.unwrap(),
+        Expr::Op(
+            Op::Sum,
+            vec![Expr::Lit(1), Expr::Op(Op::Sum, vec![Expr::Lit(2)])]
+        )
+    );
+
+    #[derive(serde_implicit::Deserialize, Debug, PartialEq)]
+    enum Text<'a> {
+        Labelled(u32, Cow<'a, str>),
+        Plain(Cow<'a, str>),
+    }
+
+    let res: Result<Text, _> = serde_json::from_value(json!([1, "one"]));
+    assert_eq!(res.unwrap(), Text::Labelled(1, "one".into()));
+
+    let res: Result<Text, _> = serde_json::from_value(json!("plain"));
+    assert_eq!(res.unwrap(), Text::Plain("plain".into()));
+
+    #[derive(serde::Deserialize, Debug, PartialEq)]
+    struct Padded<const N: usize>(u32);
+
+    #[derive(serde_implicit::Deserialize, Debug, PartialEq)]
+    enum Block<'a, T: Clone, const N: usize>
+    where
+        T: Debug,
+    {
+        Data(#[serde_implicit(tag)] Padded<N>, Cow<'a, str>, T),
+        Empty(bool),
+    }
+
+    let res: Result<Block<'_, u8, 4>, _> = serde_json::from_value(json!([7, "data", 1]));
+    assert_eq!(res.unwrap(), Block::Data(Padded(7), "data".into(), 1));
+
+    let res: Result<Block<'_, u8, 4>, _> = serde_json::from_value(json!(false));
+    assert_eq!(res.unwrap(), Block::Empty(false));
+
+    #[derive(serde_implicit::Deserialize, Debug, PartialEq)]
+    enum Record<'a, T: Clone, const N: usize>
+    where
+        T: Debug,
+    {
+        Data {
+            #[serde_implicit(tag)]
+            padded: Padded<N>,
+            name: Cow<'a, str>,
+            value: T,
+        },
+    }
+
+    let res: Result<Record<'_, u8, 4>, _> =
+        serde_json::from_value(json!({ "padded": 7, "name": "data", "value": 1 }));
+    assert_eq!(
+        res.unwrap(),
+        Record::Data {
+            padded: Padded(7),
+            name: "data".into(),
+            value: 1
+        }
+    );
+}
+
 #[test]
 fn ui() {
     let t = trybuild::TestCases::new();
@@ -386,6 +1746,7 @@ fn ui() {
 fn test_string_key_map_to_integer_key() {
     use std::collections::HashMap;
 
+    #[allow(dead_code)]
     #[derive(serde_implicit_proc::Deserialize, Debug)]
     enum WithMap {
         Variant {
@@ -525,6 +1886,7 @@ fn test_null_tag_value_skipped() {
 
 #[test]
 fn test_readme_tuples() {
+    #[allow(dead_code)]
     #[derive(serde_implicit::Deserialize, Debug)]
     enum Message {
         Literal(u64),
@@ -581,3 +1943,144 @@ fn test_missing_option_field_defaults_to_none() {
         other => panic!("expected V0, got {other:?}"),
     }
 }
+
+#[cfg(feature = "tape")]
+#[test]
+fn tape_replay() {
+    use serde::Deserialize;
+    use serde_implicit::content::Content;
+    use serde_implicit::tape::Tape;
+    use std::collections::BTreeMap;
+
+    #[derive(serde::Deserialize, Debug, PartialEq)]
+    enum Shape {
+        Circle(f64),
+        Square { side: u32 },
+        Empty,
+    }
+
+    #[derive(serde::Deserialize, Debug, PartialEq)]
+    struct Doc<'a> {
+        name: String,
+        label: &'a str,
+        scores: BTreeMap<u32, Option<i8>>,
+        shapes: Vec<Shape>,
+    }
+
+    // Escapes make `name` and the shape keys owned, copied to the tape
+    let input = r#"{
+        "name": "a \"quoted\" name",
+        "label": "plain",
+        "scores": { "1": 5, "2": null },
+        "shapes": [{ "Circle": 1.5 }, { "Square": { "side": 2 } }, "Empty"]
+    }"#;
+
+    let tape: Tape = serde_json::from_str(input).unwrap();
+    let doc = Doc::deserialize(tape.deserializer::<serde_json::Error>()).unwrap();
+    assert_eq!(
+        doc,
+        Doc {
+            name: "a \"quoted\" name".into(),
+            label: "plain",
+            scores: BTreeMap::from([(1, Some(5)), (2, None)]),
+            shapes: vec![Shape::Circle(1.5), Shape::Square { side: 2 }, Shape::Empty],
+        }
+    );
+
+    let content: Content = serde_json::from_str(input).unwrap();
+    assert_eq!(tape.to_content(), content);
+    assert_eq!(Tape::from(content.clone()).to_content(), content);
+
+    // Errors name what the tape holds, like they do for `Content`
+    let tape: Tape = serde_json::from_str(r#"{"name": 1}"#).unwrap();
+    let err = Doc::deserialize(tape.deserializer::<serde_json::Error>()).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid type: integer `1`, expected a string"
+    );
+}
+
+#[test]
+fn wide_integers() {
+    use serde::Deserialize;
+    use serde::de::value::{Error, MapDeserializer, SeqDeserializer};
+    use serde_implicit::content::Content;
+
+    #[derive(serde_implicit_proc::Deserialize, Debug, PartialEq)]
+    enum Ledger<'a> {
+        Account {
+            #[serde_implicit(tag)]
+            account: u128,
+            balance: i128,
+            flags: u8,
+        },
+        #[serde_implicit(fallthrough)]
+        Other(#[serde(borrow)] Content<'a>),
+    }
+
+    #[derive(serde_implicit_proc::Deserialize, Debug, PartialEq)]
+    enum Transfer {
+        Amount(u128, i128),
+    }
+
+    let id = u128::MAX - 1;
+    let balance = i128::MIN + 1;
+
+    // Stands in for a format with 128-bit integers, which JSON is not
+    let input = MapDeserializer::<_, Error>::new(
+        [
+            ("account", Content::from(id)),
+            ("balance", Content::from(balance)),
+            ("flags", Content::from(3u8)),
+        ]
+        .into_iter(),
+    );
+    assert_eq!(
+        Ledger::deserialize(input).unwrap(),
+        Ledger::Account {
+            account: id,
+            balance,
+            flags: 3
+        }
+    );
+
+    let input =
+        SeqDeserializer::<_, Error>::new([Content::from(id), Content::from(balance)].into_iter());
+    assert_eq!(
+        Transfer::deserialize(input).unwrap(),
+        Transfer::Amount(id, balance)
+    );
+
+    // Like without buffering, a `u128` is not narrowed to a smaller field
+    let input = MapDeserializer::<_, Error>::new(
+        [
+            ("account", Content::from(1u8)),
+            ("flags", Content::from(id)),
+        ]
+        .into_iter(),
+    );
+    let err = Ledger::deserialize(input).unwrap_err();
+    assert!(err.to_string().contains("u128"), "{err}");
+
+    // Widths are kept, a fallthrough sees the integer it was given
+    let input = SeqDeserializer::<_, Error>::new([Content::from(5u16)].into_iter());
+    assert_eq!(
+        Ledger::deserialize(input).unwrap(),
+        Ledger::Other(Content::Seq(vec![Content::U16(5)]))
+    );
+
+    assert_eq!(Content::from(id).as_u128(), Some(id));
+    assert_eq!(Content::from(id).as_u64(), None);
+    assert_eq!(Content::from(7u128).as_u64(), Some(7));
+    assert_eq!(Content::from(-7i128).as_i64(), Some(-7));
+    assert_eq!(Content::from(balance).as_i128(), Some(balance));
+    assert_eq!(Content::from(balance).as_u128(), None);
+    assert_eq!(Content::from(7u8).as_i128(), Some(7));
+    assert_eq!(Content::from(u64::MAX).as_i128(), Some(u64::MAX as i128));
+    assert_eq!(Content::from(1u128 << 64).as_f64(), Some(2f64.powi(64)));
+    assert_ne!(Content::U128(1), Content::U64(1));
+    assert_eq!(
+        serde_json::to_string(&Content::from(id)).unwrap(),
+        id.to_string()
+    );
+}
@@ -4,6 +4,7 @@ use proptest::proptest;
 use proptest_arbitrary_interop::arb;
 use proptest_derive::Arbitrary;
 
+#[allow(clippy::enum_variant_names)]
 #[derive(serde_implicit_proc::Deserialize, serde::Serialize, Debug, PartialEq, Arbitrary)]
 #[serde(untagged)]
 enum MultiTypeTag {
@@ -63,6 +64,7 @@ enum NestedEnum {
     },
 }
 
+#[allow(dead_code)]
 #[derive(serde_implicit_proc::Deserialize, serde::Serialize, Debug, PartialEq)]
 enum RecursiveEnum {
     Leaf {
@@ -78,6 +80,7 @@ enum RecursiveEnum {
     },
 }
 
+#[allow(dead_code)]
 mod edge_cases {
     #[derive(serde_implicit_proc::Deserialize, serde::Serialize, Debug, PartialEq)]
     enum EmptyEnum {}
@@ -102,6 +105,7 @@ enum TupleEnum {
 }
 
 /// Tuple enum with custom tag positions via #[serde_implicit(tag)]
+#[allow(clippy::enum_variant_names)]
 #[derive(serde_implicit::Deserialize, serde::Serialize, Debug, PartialEq, Arbitrary)]
 #[serde(untagged)]
 enum TupleCustomTag {
@@ -152,6 +156,7 @@ proptest! {
     // the only difference should be in their behavior in error messages
     #[test]
     fn test_agrees_with_serde(rand in any::<MultiTypeTag>()) {
+        #[allow(clippy::enum_variant_names)]
         #[derive(serde::Deserialize, serde::Serialize, Debug, PartialEq)]
         #[serde(untagged)]
         enum SerdeMultiTypeTag {
@@ -229,4 +234,26 @@ proptest! {
         let deserialized: TupleFlatten = serde_json::from_value(serialized).unwrap();
         assert_eq!(value, deserialized);
     }
+
+    /// A tape replays arbitrary JSON the same as parsing it directly, and
+    /// holds the same `Content`.
+    #[cfg(feature = "tape")]
+    #[test]
+    fn test_tape_roundtrip(json in arb::<ArbitraryValue>()) {
+        use serde::Deserialize;
+        use serde_implicit::content::Content;
+        use serde_implicit::tape::Tape;
+
+        let input = serde_json::to_string(&serde_json::Value::from(json)).unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&input).unwrap();
+
+        let tape: Tape = serde_json::from_str(&input).unwrap();
+        let replayed =
+            serde_json::Value::deserialize(tape.deserializer::<serde_json::Error>()).unwrap();
+        assert_eq!(parsed, replayed);
+
+        let content: Content = serde_json::from_str(&input).unwrap();
+        assert_eq!(tape.to_content(), content);
+        assert_eq!(Tape::from(content.clone()).to_content(), content);
+    }
 }
@@ -0,0 +1,11 @@
+#[derive(serde_implicit_proc::Deserialize)]
+enum Misplaced {
+    #[serde_implicit(fallthrough)]
+    Other { value: u32 },
+    Tagged {
+        #[serde_implicit(tag)]
+        only_tag: u32,
+    },
+}
+
+fn main() {}
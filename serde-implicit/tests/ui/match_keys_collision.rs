@@ -0,0 +1,14 @@
+#[derive(serde_implicit_proc::Deserialize)]
+#[serde_implicit(match_keys = "normalized")]
+enum Collision {
+    Snake {
+        #[serde_implicit(tag)]
+        image_url: String,
+    },
+    Camel {
+        #[serde_implicit(tag)]
+        imageUrl: String,
+    },
+}
+
+fn main() {}
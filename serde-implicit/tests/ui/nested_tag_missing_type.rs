@@ -0,0 +1,7 @@
+#[derive(serde_implicit_proc::Deserialize)]
+enum MissingType {
+    Nested(#[serde_implicit(tag, path = "0")] (String, u32), bool),
+    Plain(u64),
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+#[derive(serde_implicit_proc::Deserialize)]
+enum Optional {
+    Tombstone {
+        #[serde_implicit(tag)]
+        deleted_at: Option<u64>,
+    },
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+#[derive(serde_implicit_proc::Deserialize)]
+struct Checkout {
+    #[serde_implicit(requires = "billing_nme")]
+    billing_address: Option<String>,
+    billing_name: Option<String>,
+}
+
+fn main() {}
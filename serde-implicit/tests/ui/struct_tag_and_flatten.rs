@@ -0,0 +1,16 @@
+use std::collections::HashMap;
+
+#[derive(serde_implicit_proc::Deserialize)]
+enum BadAnnotation {
+    Normal {
+        #[serde_implicit(tag)]
+        id: u64,
+    },
+    Conflicting {
+        #[serde_implicit(tag)]
+        #[serde(flatten)]
+        rest: HashMap<String, u64>,
+    },
+}
+
+fn main() {}
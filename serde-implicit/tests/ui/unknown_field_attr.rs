@@ -0,0 +1,9 @@
+#[derive(serde_implicit_proc::Deserialize)]
+enum Event {
+    Click {
+        #[serde_implicit(tag, rename = "btn")]
+        button: u8,
+    },
+}
+
+fn main() {}
@@ -0,0 +1,11 @@
+#[derive(serde_implicit_proc::Deserialize)]
+enum Event {
+    Click {
+        #[serde_implicit(tag)]
+        button: u8,
+        #[serde(rename = "lbl")]
+        label: String,
+    },
+}
+
+fn main() {}
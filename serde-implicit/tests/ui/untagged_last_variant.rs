@@ -0,0 +1,12 @@
+#[derive(serde_implicit_proc::Deserialize)]
+enum Forgotten {
+    Tagged {
+        #[serde_implicit(tag)]
+        only_tag: u32,
+    },
+    Untagged {
+        value: u32,
+    },
+}
+
+fn main() {}